@@ -0,0 +1,212 @@
+use crate::{consts::*, instance::Transform, scene_graph::SceneGraph, shader::Varyings};
+use cgmath::*;
+use serde::Deserialize;
+
+/// How a track fills in the values between two keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Interpolation {
+    /// Holds each key's value until the next key.
+    Step,
+    #[default]
+    Linear,
+    /// Catmull-Rom spline, passing through the keys without turning sharply at them.
+    Cubic,
+}
+
+/// Values over time, given by `(time, value)` keys with the time in seconds. Before
+/// the first key and after the last the track holds their values.
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    pub interpolation: Interpolation,
+    keys: Vec<(f64, T)>,
+}
+impl<T: Copy> Track<T> {
+    /// Keys may come in any order.
+    pub fn new(interpolation: Interpolation, mut keys: Vec<(f64, T)>) -> Self {
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            interpolation,
+            keys,
+        }
+    }
+
+    /// Time of the last key.
+    pub fn duration(&self) -> f64 {
+        self.keys.last().map_or(0., |&(time, _)| time)
+    }
+
+    /// The key `time` is after and how far along it is towards the next key, from 0 to 1.
+    /// `None` for a track without keys.
+    fn segment(&self, time: f64) -> Option<(usize, f64)> {
+        let last = self.keys.len().checked_sub(1)?;
+        let next = self.keys.partition_point(|&(t, _)| t <= time);
+        if next == 0 {
+            return Some((0, 0.));
+        }
+        if next > last {
+            return Some((last, 0.));
+        }
+        let (from, to) = (self.keys[next - 1].0, self.keys[next].0);
+        Some((next - 1, (time - from) / (to - from)))
+    }
+
+    /// Values of the keys before `i`, at `i`, and the two after it, repeating the first
+    /// and last key past the ends.
+    fn around(&self, i: usize) -> [T; 4] {
+        let last = self.keys.len() - 1;
+        let at = |j: usize| self.keys[j.min(last)].1;
+        [at(i.saturating_sub(1)), at(i), at(i + 1), at(i + 2)]
+    }
+}
+impl<T: Varyings> Track<T> {
+    pub fn sample(&self, time: f64) -> Option<T> {
+        let (i, t) = self.segment(time)?;
+        let [before, from, to, after] = self.around(i);
+        Some(match self.interpolation {
+            Interpolation::Step => from,
+            Interpolation::Linear => from.scale(1. - t).add(to.scale(t)),
+            Interpolation::Cubic => catmull_rom([before, from, to, after], t),
+        })
+    }
+}
+impl Track<f64> {
+    /// Samples a track of rotations about y in degrees. Neighbouring keys are joined the
+    /// short way round, so a full turn needs at least three keys.
+    pub fn sample_rotation(&self, time: f64) -> Option<f64> {
+        let (i, t) = self.segment(time)?;
+        let [before, from, to, after] = self.around(i);
+        Some(match self.interpolation {
+            Interpolation::Step => from,
+            Interpolation::Linear => {
+                let q_from = Quaternion::from_angle_y(Deg(from));
+                let q_to = Quaternion::from_angle_y(Deg(to));
+                let q = q_from.slerp(q_to, t);
+                let angle = 2. * q.v.y.atan2(q.s).to_degrees();
+                // Stay next to `from` rather than jumping to wherever atan2 lands.
+                from + wrap_degrees(angle - from)
+            }
+            Interpolation::Cubic => {
+                let before = from - wrap_degrees(from - before);
+                let to = from + wrap_degrees(to - from);
+                let after = to + wrap_degrees(after - to);
+                catmull_rom([before, from, to, after], t)
+            }
+        })
+    }
+}
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Self {
+            interpolation: Interpolation::default(),
+            keys: Vec::new(),
+        }
+    }
+}
+
+/// Moves one scene graph node, or one joint when it's part of a `Clip`. Parts of
+/// its transform without keys are left alone.
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    pub node: usize,
+    pub translation: Track<Vec3>,
+    pub rotation: Track<f64>,
+    pub scale: Track<f64>,
+}
+impl Animation {
+    pub fn duration(&self) -> f64 {
+        self.translation
+            .duration()
+            .max(self.rotation.duration())
+            .max(self.scale.duration())
+    }
+
+    pub fn apply(&self, time: f64, local: &mut Transform) {
+        if let Some(translation) = self.translation.sample(time) {
+            local.translation = translation;
+        }
+        if let Some(rot) = self.rotation.sample_rotation(time) {
+            local.rot = rot;
+        }
+        if let Some(scale) = self.scale.sample(time) {
+            local.scale = scale;
+        }
+    }
+}
+
+/// Poses the graph's nodes at `time`, starting from their rest transforms. Run
+/// `SceneGraph::update` afterwards.
+pub fn animate(animations: &[Animation], time: f64, graph: &mut SceneGraph) {
+    for node in &mut graph.nodes {
+        node.local = node.rest;
+    }
+    for animation in animations {
+        animation.apply(time, &mut graph.nodes[animation.node].local);
+    }
+}
+
+/// Length of the longest animation.
+pub fn duration(animations: &[Animation]) -> f64 {
+    animations
+        .iter()
+        .map(Animation::duration)
+        .fold(0., f64::max)
+}
+
+/// Playback time shared by the scene's animations.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Clock {
+    /// Seconds since the start of the animations.
+    #[serde(skip)]
+    pub time: f64,
+    pub playing: bool,
+    /// Start over once the longest animation ends, rather than stopping there.
+    pub looping: bool,
+    /// How many seconds of animation pass per second, below 0 plays backwards.
+    pub speed: f64,
+}
+impl Clock {
+    /// Moves time on by `dt` seconds, unless paused.
+    pub fn advance(&mut self, dt: f64, duration: f64) {
+        if !self.playing {
+            return;
+        }
+        self.time += dt * self.speed;
+        self.time = if self.looping && duration > 0. {
+            self.time.rem_euclid(duration)
+        } else {
+            self.time.clamp(0., duration)
+        };
+    }
+
+    pub fn toggle_playing(&mut self) {
+        self.playing = !self.playing;
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.;
+    }
+}
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            time: 0.,
+            playing: true,
+            looping: true,
+            speed: 1.,
+        }
+    }
+}
+
+fn catmull_rom<T: Varyings>([p0, p1, p2, p3]: [T; 4], t: f64) -> T {
+    let (t2, t3) = (t * t, t * t * t);
+    p0.scale(0.5 * (-t + 2. * t2 - t3))
+        .add(p1.scale(0.5 * (2. - 5. * t2 + 3. * t3)))
+        .add(p2.scale(0.5 * (t + 4. * t2 - 3. * t3)))
+        .add(p3.scale(0.5 * (t3 - t2)))
+}
+
+/// The same angle in degrees, between -180 and 180.
+pub fn wrap_degrees(angle: f64) -> f64 {
+    (angle + 180.).rem_euclid(360.) - 180.
+}
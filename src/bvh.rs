@@ -0,0 +1,399 @@
+use crate::{clipping::Aabb, consts::*, instance::Instance};
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+pub struct BvhNode {
+    /// World space box around every instance below the node.
+    pub bounds: Aabb,
+    /// Where the node's instances are in `Bvh::instances`.
+    pub range: Range<usize>,
+    /// `None` for leaves.
+    pub children: Option<[usize; 2]>,
+}
+
+/// Bounding volume hierarchy over the world space boxes of the scene's instances, so
+/// culling and picking can skip whole groups of nearby instances with one test.
+/// Nodes come before their children, the root first.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    /// Instance indices, ordered so every node's instances are next to each other.
+    pub instances: Vec<usize>,
+    /// Total surface area of the nodes when the tree was built.
+    built_area: f64,
+}
+impl Bvh {
+    pub fn new(instances: &[Instance]) -> Self {
+        let mut bvh = Self::default();
+        bvh.build(instances);
+        bvh
+    }
+
+    /// Instances below a node.
+    pub fn instances_of(&self, node: usize) -> &[usize] {
+        &self.instances[self.nodes[node].range.clone()]
+    }
+
+    /// Keeps the tree around the instances' current transforms. Run it after
+    /// `SceneGraph::update`. Refitting keeps the tree's shape, so it gets rebuilt when
+    /// instances were added or removed, or once moving them has made the boxes much
+    /// looser than a fresh tree's.
+    pub fn update(&mut self, instances: &[Instance]) {
+        if self.instances.len() != instances.len() {
+            self.build(instances);
+            return;
+        }
+        self.refit(instances);
+        if self.total_area() > self.built_area * BVH_REBUILD_GROWTH {
+            self.build(instances);
+        }
+    }
+
+    /// Splits the instances in two at the middle of their centers along the axis those
+    /// spread out the most on, until leaves have at most `BVH_LEAF_SIZE` of them.
+    pub fn build(&mut self, instances: &[Instance]) {
+        let boxes: Vec<Aabb> = instances.iter().map(world_box).collect();
+        self.nodes.clear();
+        self.instances = (0..instances.len()).collect();
+        if !instances.is_empty() {
+            self.build_node(0..instances.len(), &boxes);
+        }
+        self.built_area = self.total_area();
+    }
+
+    /// Recomputes the boxes without changing which instances are below which node.
+    pub fn refit(&mut self, instances: &[Instance]) {
+        // Children come after their parents, so walking backwards finishes both
+        // children before the node above them.
+        for i in (0..self.nodes.len()).rev() {
+            let bounds = match self.nodes[i].children {
+                Some([left, right]) => self.nodes[left].bounds.merge(self.nodes[right].bounds),
+                None => self
+                    .instances_of(i)
+                    .iter()
+                    .map(|&inst| world_box(&instances[inst]))
+                    .reduce(Aabb::merge)
+                    .expect("leaves aren't empty"),
+            };
+            self.nodes[i].bounds = bounds;
+        }
+    }
+
+    fn build_node(&mut self, range: Range<usize>, boxes: &[Aabb]) -> usize {
+        let members = &mut self.instances[range.clone()];
+        let bounds = members
+            .iter()
+            .map(|&i| boxes[i])
+            .reduce(Aabb::merge)
+            .expect("nodes aren't empty");
+        let index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds,
+            range: range.clone(),
+            children: None,
+        });
+        if range.len() <= BVH_LEAF_SIZE {
+            return index;
+        }
+
+        let centers = Aabb::new(&members.iter().map(|&i| boxes[i].center()).collect());
+        let extents = centers.half_extents();
+        let axis = (0..3)
+            .max_by(|&a, &b| extents[a].total_cmp(&extents[b]))
+            .unwrap();
+        let middle = range.len() / 2;
+        members.select_nth_unstable_by(middle, |&a, &b| {
+            boxes[a].center()[axis].total_cmp(&boxes[b].center()[axis])
+        });
+        let left = self.build_node(range.start..range.start + middle, boxes);
+        let right = self.build_node(range.start + middle..range.end, boxes);
+        self.nodes[index].children = Some([left, right]);
+        index
+    }
+
+    fn total_area(&self) -> f64 {
+        self.nodes
+            .iter()
+            .map(|node| node.bounds.surface_area())
+            .sum()
+    }
+}
+
+fn world_box(instance: &Instance) -> Aabb {
+    instance.aabb.transformed(instance.trans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clipping::{clip_scene, CullingVolume, Obb, Plane},
+        instance::{Model, Transform},
+        material::{Material, RenderMode},
+        picking::{self, Ray},
+    };
+    use cgmath::InnerSpace;
+
+    /// Instances scattered in front of the camera, turned and sized differently.
+    fn scatter(count: usize) -> Vec<Instance> {
+        let mut seed = 12345u64;
+        let mut random = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as f64 / (1u64 << 31) as f64
+        };
+        (0..count)
+            .map(|i| {
+                let model = if i % 2 == 0 {
+                    Model::Cube
+                } else {
+                    Model::Icosphere { subdivisions: 1 }
+                };
+                let translation = Vec3::new(
+                    random() * 20. - 10.,
+                    random() * 8. - 4.,
+                    random() * 20. - 5.,
+                );
+                let material = Material::new(WHITE, RenderMode::Shaded);
+                let mut instance = Instance::new(model, translation, 0.3 + random(), material);
+                instance.trans.rot = random() * 360.;
+                instance
+            })
+            .collect()
+    }
+
+    fn encloses(outer: Aabb, inner: Aabb) -> bool {
+        (0..3).all(|i| outer.min[i] <= inner.min[i] && inner.max[i] <= outer.max[i])
+    }
+
+    /// Checks that the nodes split their instances between their children, leaves
+    /// aren't too big and every box holds what's below it.
+    fn check(bvh: &Bvh, instances: &[Instance]) {
+        let mut sorted = bvh.instances.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..instances.len()).collect::<Vec<_>>());
+        for (i, node) in bvh.nodes.iter().enumerate() {
+            match node.children {
+                Some([left, right]) => {
+                    assert!(left > i && right > i);
+                    let (left, right) = (&bvh.nodes[left], &bvh.nodes[right]);
+                    assert_eq!(left.range.start, node.range.start);
+                    assert_eq!(left.range.end, right.range.start);
+                    assert_eq!(right.range.end, node.range.end);
+                    assert!(encloses(node.bounds, left.bounds));
+                    assert!(encloses(node.bounds, right.bounds));
+                }
+                None => {
+                    assert!(!node.range.is_empty() && node.range.len() <= BVH_LEAF_SIZE);
+                    for &inst in bvh.instances_of(i) {
+                        assert!(encloses(node.bounds, world_box(&instances[inst])));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The view frustum's planes as `(normal, d)`, points in front of all of them being
+    /// inside.
+    fn frustum() -> Vec<(Vec3, f64)> {
+        let s = 1. / 2f64.sqrt();
+        vec![
+            (Vec3::new(0., 0., 1.), -D),
+            (Vec3::new(s, 0., s), 0.),
+            (Vec3::new(-s, 0., s), 0.),
+            (Vec3::new(0., s, s), 0.),
+            (Vec3::new(0., -s, s), 0.),
+        ]
+    }
+
+    /// Whether a camera space box is at least partly in front of every plane.
+    fn box_visible(obb: Obb, planes: &[(Vec3, f64)]) -> bool {
+        planes.iter().all(|&(normal, d)| {
+            let reach: f64 = (0..3)
+                .map(|i| normal.dot(obb.axes[i]).abs() * obb.half_extents[i])
+                .sum();
+            normal.dot(obb.center) + d >= -reach
+        })
+    }
+
+    /// The instances whose culling volume is at least partly inside the frustum, tested
+    /// one by one.
+    fn visible_one_by_one(
+        instances: &[Instance],
+        planes: &[(Vec3, f64)],
+        cam_trans: Transform,
+        volume: CullingVolume,
+    ) -> Vec<usize> {
+        let visible = |instance: &Instance| {
+            let trans = instance.trans;
+            match volume {
+                CullingVolume::Sphere => {
+                    let sphere = instance.bounding_sphere.transformed(trans);
+                    let sphere = sphere.to_camera_space(cam_trans);
+                    planes
+                        .iter()
+                        .all(|&(normal, d)| normal.dot(sphere.center) + d >= -sphere.radius)
+                }
+                CullingVolume::Aabb => {
+                    let obb = instance.aabb.transformed(trans).to_obb();
+                    box_visible(obb.to_camera_space(cam_trans), planes)
+                }
+                CullingVolume::Obb => {
+                    let obb = instance.obb.transformed(trans);
+                    box_visible(obb.to_camera_space(cam_trans), planes)
+                }
+            }
+        };
+        (0..instances.len())
+            .filter(|&i| visible(&instances[i]))
+            .collect()
+    }
+
+    /// The nearest triangle under a canvas point beyond the near plane, trying every
+    /// triangle of every instance.
+    fn pick_one_by_one(
+        instances: &[Instance],
+        cam_trans: Transform,
+        point: Vec2,
+    ) -> Option<(usize, usize)> {
+        let ray = Ray::through_canvas(point);
+        let mut best: Option<(usize, usize, f64)> = None;
+        for (i, instance) in instances.iter().enumerate() {
+            let transformed = instance.camera_space_verts(cam_trans);
+            for (triangle, tri) in instance.mesh.triangles.iter().enumerate() {
+                let corners = [tri.0, tri.1, tri.2].map(|v| transformed[v]);
+                let Some((t, _)) = ray.hit_triangle(corners) else {
+                    continue;
+                };
+                if ray.at(t).z >= D && best.is_none_or(|(_, _, best)| t < best) {
+                    best = Some((i, triangle, t));
+                }
+            }
+        }
+        best.map(|(i, triangle, _)| (i, triangle))
+    }
+
+    fn cameras() -> Vec<Transform> {
+        (0..8)
+            .map(|i| {
+                let mut cam_trans = Transform::new(Vec3::new(i as f64 - 4., 0., -2.), 1.);
+                cam_trans.rot = i as f64 * 45.;
+                cam_trans
+            })
+            .collect()
+    }
+
+    #[test]
+    fn build() {
+        for count in [0, 1, BVH_LEAF_SIZE, BVH_LEAF_SIZE + 1, 50] {
+            let instances = scatter(count);
+            let bvh = Bvh::new(&instances);
+            assert_eq!(bvh.nodes.is_empty(), count == 0);
+            check(&bvh, &instances);
+        }
+    }
+
+    #[test]
+    fn refit_keeps_the_shape() {
+        let mut instances = scatter(50);
+        let mut bvh = Bvh::new(&instances);
+        let (nodes, order) = (bvh.nodes.clone(), bvh.instances.clone());
+        for instance in &mut instances {
+            instance.trans.translation.y += 0.5;
+            instance.trans.rot += 30.;
+        }
+        bvh.refit(&instances);
+        check(&bvh, &instances);
+        assert_eq!(bvh.instances, order);
+        for (node, before) in bvh.nodes.iter().zip(&nodes) {
+            assert_eq!(node.children, before.children);
+            assert_eq!(node.range, before.range);
+        }
+    }
+
+    #[test]
+    fn update_rebuilds() {
+        let mut instances = scatter(50);
+        let mut bvh = Bvh::new(&instances);
+
+        // Small moves only refit.
+        let order = bvh.instances.clone();
+        instances[0].trans.translation.x += 0.1;
+        bvh.update(&instances);
+        assert_eq!(bvh.instances, order);
+
+        // Swapping instances across the scene loosens the boxes past the limit.
+        let count = instances.len();
+        for i in 0..count / 2 {
+            let (a, b) = (instances[i].trans, instances[count - 1 - i].trans);
+            instances[i].trans = b;
+            instances[count - 1 - i].trans = a;
+        }
+        bvh.update(&instances);
+        assert_eq!(bvh.total_area(), bvh.built_area);
+        check(&bvh, &instances);
+
+        instances.truncate(30);
+        bvh.update(&instances);
+        check(&bvh, &instances);
+    }
+
+    #[test]
+    fn culling_matches_one_by_one() {
+        let instances = scatter(60);
+        let bvh = Bvh::new(&instances);
+        let frustum = frustum();
+        let planes: Vec<Plane> = frustum.iter().map(|&(n, d)| Plane::new(n, d)).collect();
+        let mut seen = 0;
+        for cam_trans in cameras() {
+            for volume in [
+                CullingVolume::Sphere,
+                CullingVolume::Aabb,
+                CullingVolume::Obb,
+            ] {
+                let visible = clip_scene(&bvh, &instances, &planes, cam_trans, volume);
+                let mut with_bvh: Vec<usize> = visible
+                    .iter()
+                    .map(|&v| instances.iter().position(|i| std::ptr::eq(i, v)).unwrap())
+                    .collect();
+                with_bvh.sort();
+                let expected = visible_one_by_one(&instances, &frustum, cam_trans, volume);
+                if volume == CullingVolume::Sphere {
+                    // Spheres reach out of the node boxes, so the BVH also culls what's
+                    // in view of the sphere but not of the box.
+                    let boxes =
+                        visible_one_by_one(&instances, &frustum, cam_trans, CullingVolume::Aabb);
+                    assert!(with_bvh.iter().all(|i| expected.contains(i)));
+                    assert!(expected
+                        .iter()
+                        .filter(|i| boxes.contains(i))
+                        .all(|i| with_bvh.contains(i)));
+                } else {
+                    assert_eq!(with_bvh, expected);
+                }
+                seen += with_bvh.len();
+            }
+        }
+        assert!(seen > 0);
+    }
+
+    #[test]
+    fn picking_matches_one_by_one() {
+        let instances = scatter(60);
+        let bvh = Bvh::new(&instances);
+        let mut hits = 0;
+        for cam_trans in cameras() {
+            for y in (-400..=400).step_by(80) {
+                for x in (-400..=400).step_by(80) {
+                    let point = Vec2::new(x as f64, y as f64);
+                    let with_bvh = picking::pick(&instances, &bvh, cam_trans, point);
+                    let with_bvh = with_bvh.map(|hit| (hit.instance, hit.triangle));
+                    assert_eq!(with_bvh, pick_one_by_one(&instances, cam_trans, point));
+                    hits += with_bvh.is_some() as usize;
+                }
+            }
+        }
+        assert!(hits > 0);
+    }
+}
@@ -1,9 +1,13 @@
 use crate::{
+    bvh::Bvh,
     consts::*,
-    instance::{Instance},
+    instance::{Instance, Transform},
 };
 use cgmath::*;
 
+/// Rotations `Obb::new` applies to each pair of axes, plenty for 3x3 matrices.
+const JACOBI_SWEEPS: usize = 8;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Plane {
     normal: Vec3,
@@ -17,21 +21,21 @@ impl Plane {
 
 #[derive(Debug, Clone, Copy)]
 pub struct BoundingSphere {
-    center: Vec3,
-    orig_center: Vec3,
-    radius: f64,
+    pub center: Vec3,
+    pub radius: f64,
 }
 impl BoundingSphere {
+    /// Sphere around the centroid of `verts`, reaching the vertex furthest from it. No
+    /// vertices give a point at the origin.
     pub fn new(verts: &Vertices) -> Self {
         let (center, radius) = BoundingSphere::compute(verts);
-        Self {
-            center,
-            radius,
-            orig_center: center,
-        }
+        Self { center, radius }
     }
 
     fn compute(verts: &Vertices) -> (Vec3, f64) {
+        if verts.is_empty() {
+            return (Vec3::zero(), 0.);
+        }
         let mut total = 0.;
         let mut total_vert_pos = Vec3::new(0., 0., 0.);
         for v in verts {
@@ -48,55 +52,397 @@ impl BoundingSphere {
         (center, furthest_away)
     }
 
-    pub fn update(&mut self, trans: Vec3, cam_rot: f64) {
-        let rot_cam: Basis3<f64> = Rotation3::from_angle_y(Deg(cam_rot));
-        self.center = trans + self.orig_center + DEFAULT_TRANSL;
-        self.center = rot_cam.rotate_vector(self.center);
-        //pre(self.center);
+    /// Moves a sphere given in the space of `trans` into the space `trans` maps to.
+    pub fn transformed(self, trans: Transform) -> Self {
+        Self {
+            center: trans.to_parent_space(self.center),
+            radius: self.radius * trans.scale,
+        }
+    }
+
+    pub fn to_camera_space(self, cam_trans: Transform) -> Self {
+        Self {
+            center: cam_trans.to_camera_space(self.center),
+            radius: self.radius,
+        }
+    }
+
+    /// Smallest sphere containing both spheres.
+    pub fn merge(self, other: Self) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return self;
+        }
+        if distance + self.radius <= other.radius {
+            return other;
+        }
+        let radius = (distance + self.radius + other.radius) / 2.;
+        Self {
+            center: self.center + offset * ((radius - self.radius) / distance),
+            radius,
+        }
+    }
+
+    fn classify(self, planes: &[Plane]) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in planes {
+            let d = signed_dist(plane, self.center);
+            if d < -self.radius {
+                return Containment::Outside;
+            }
+            if d < self.radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+}
+
+/// Box aligned with the axes of the space it's in.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+impl Aabb {
+    /// No vertices give a point at the origin.
+    pub fn new(verts: &Vertices) -> Self {
+        let first = verts.first().copied().unwrap_or_else(Vec3::zero);
+        let mut aabb = Self {
+            min: first,
+            max: first,
+        };
+        for v in verts {
+            aabb.min = Vec3::new(
+                aabb.min.x.min(v.x),
+                aabb.min.y.min(v.y),
+                aabb.min.z.min(v.z),
+            );
+            aabb.max = Vec3::new(
+                aabb.max.x.max(v.x),
+                aabb.max.y.max(v.y),
+                aabb.max.z.max(v.z),
+            );
+        }
+        aabb
+    }
+
+    pub fn center(self) -> Vec3 {
+        (self.min + self.max) / 2.
+    }
+
+    pub fn half_extents(self) -> Vec3 {
+        (self.max - self.min) / 2.
+    }
+
+    /// Smallest box in the space `trans` maps to that holds this one. It grows as the
+    /// box gets turned, `Obb` keeps fitting tightly.
+    pub fn transformed(self, trans: Transform) -> Self {
+        let center = trans.to_parent_space(self.center());
+        let half = self.half_extents() * trans.scale;
+        // Each new half extent gathers how far the turned axes reach along it.
+        let axes = [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()].map(|axis| trans.rotate(axis));
+        let reach = |i: usize| (0..3).map(|j| axes[j][i].abs() * half[j]).sum::<f64>();
+        let half = Vec3::new(reach(0), reach(1), reach(2));
+        Self {
+            min: center - half,
+            max: center + half,
+        }
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            min: Vec3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vec3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn surface_area(self) -> f64 {
+        let size = self.max - self.min;
+        2. * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// The same box as an `Obb`, which can be turned into camera space.
+    pub fn to_obb(self) -> Obb {
+        Obb {
+            center: self.center(),
+            axes: [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()],
+            half_extents: self.half_extents(),
+        }
+    }
+}
+
+/// Box turned to follow the mesh. Elongated or diagonal meshes fit it much more tightly
+/// than a sphere or an `Aabb`.
+#[derive(Debug, Clone, Copy)]
+pub struct Obb {
+    pub center: Vec3,
+    /// Unit length and perpendicular to each other.
+    pub axes: [Vec3; 3],
+    pub half_extents: Vec3,
+}
+impl Obb {
+    /// Box along the principal axes of the vertices, the directions they spread out the
+    /// most and least in. No vertices give a point at the origin.
+    pub fn new(verts: &Vertices) -> Self {
+        if verts.is_empty() {
+            return Self {
+                center: Vec3::zero(),
+                axes: [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()],
+                half_extents: Vec3::zero(),
+            };
+        }
+        let count = verts.len() as f64;
+        let mean = verts.iter().fold(Vec3::zero(), |sum, &v| sum + v) / count;
+        let mut covariance = [[0.; 3]; 3];
+        for v in verts {
+            let d = v - mean;
+            for (i, row) in covariance.iter_mut().enumerate() {
+                for (j, c) in row.iter_mut().enumerate() {
+                    *c += d[i] * d[j] / count;
+                }
+            }
+        }
+        let axes = symmetric_eigenvectors(covariance);
+
+        let mut min = Vec3::from_value(f64::MAX);
+        let mut max = Vec3::from_value(f64::MIN);
+        for v in verts {
+            for (i, axis) in axes.iter().enumerate() {
+                let along = v.dot(*axis);
+                min[i] = min[i].min(along);
+                max[i] = max[i].max(along);
+            }
+        }
+        let middle = (min + max) / 2.;
+        Self {
+            center: axes[0] * middle.x + axes[1] * middle.y + axes[2] * middle.z,
+            axes,
+            half_extents: (max - min) / 2.,
+        }
+    }
+
+    /// Moves a box given in the space of `trans` into the space `trans` maps to.
+    pub fn transformed(self, trans: Transform) -> Self {
+        Self {
+            center: trans.to_parent_space(self.center),
+            axes: self.axes.map(|axis| trans.rotate(axis)),
+            half_extents: self.half_extents * trans.scale,
+        }
+    }
+
+    pub fn to_camera_space(self, cam_trans: Transform) -> Self {
+        Self {
+            center: cam_trans.to_camera_space(self.center),
+            axes: self.axes.map(|axis| cam_trans.rotate_to_camera(axis)),
+            half_extents: self.half_extents,
+        }
+    }
+
+    fn classify(self, planes: &[Plane]) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in planes {
+            // How far the box reaches towards and away from the plane.
+            let reach: f64 = (0..3)
+                .map(|i| self.half_extents[i] * plane.normal.dot(self.axes[i]).abs())
+                .sum();
+            let d = signed_dist(plane, self.center);
+            if d < -reach {
+                return Containment::Outside;
+            }
+            if d < reach {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+}
+
+/// Eigenvectors of a symmetric matrix, found with Jacobi rotations.
+fn symmetric_eigenvectors(mut a: [[f64; 3]; 3]) -> [Vec3; 3] {
+    let mut v = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+    for _ in 0..JACOBI_SWEEPS {
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q].abs() < 1e-12 {
+                continue;
+            }
+            // The rotation in the p-q plane that zeroes a[p][q].
+            let theta = (a[q][q] - a[p][p]) / (2. * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.).sqrt());
+            let c = 1. / (t * t + 1.).sqrt();
+            let s = t * c;
+            for row in a.iter_mut().chain(v.iter_mut()) {
+                let (kp, kq) = (row[p], row[q]);
+                row[p] = c * kp - s * kq;
+                row[q] = s * kp + c * kq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            for k in 0..3 {
+                a[p][k] = c * row_p[k] - s * row_q[k];
+                a[q][k] = s * row_p[k] + c * row_q[k];
+            }
+        }
     }
+    [0, 1, 2].map(|i| Vec3::new(v[0][i], v[1][i], v[2][i]))
 }
 
-pub fn clip_scene(instances: &Vec<Instance>, planes: &Vec<Plane>) -> Vec<Instance> {
-    let mut clipped_instances = Vec::new();
-    for inst in instances {
-        let clipped_instance = clip_instance(inst, planes);
-        if let Some(clipped) = clipped_instance {
-            clipped_instances.push(clipped);
+/// What `clip_scene` tests instances with. BVH nodes are always tested with their
+/// world space boxes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CullingVolume {
+    #[default]
+    Sphere,
+    Aabb,
+    Obb,
+}
+impl CullingVolume {
+    pub fn next(self) -> Self {
+        match self {
+            CullingVolume::Sphere => CullingVolume::Aabb,
+            CullingVolume::Aabb => CullingVolume::Obb,
+            CullingVolume::Obb => CullingVolume::Sphere,
         }
     }
-    clipped_instances
 }
 
-fn clip_instance(inst: &Instance, planes: &Vec<Plane>) -> Option<Instance> {
-    for p in planes {
-        let instance = clip_instance_against_plane(inst, p);
-        if let Some(_) = instance {
-            continue;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Containment {
+    Inside,
+    Intersecting,
+    Outside,
+}
+
+/// Returns the instances at least partly inside the planes, walking the BVH so a
+/// group of nearby instances entirely inside or outside is decided by a single box
+/// test. Instances crossing a plane are kept; the renderer drops their triangles that
+/// reach past the near plane.
+pub fn clip_scene<'a>(
+    bvh: &Bvh,
+    instances: &'a [Instance],
+    planes: &[Plane],
+    cam_trans: Transform,
+    volume: CullingVolume,
+) -> Vec<&'a Instance> {
+    let mut visible = Vec::new();
+    if !bvh.nodes.is_empty() {
+        clip_node(bvh, 0, instances, planes, (cam_trans, volume), &mut visible);
+    }
+    visible
+}
+
+fn clip_node<'a>(
+    bvh: &Bvh,
+    node_index: usize,
+    instances: &'a [Instance],
+    planes: &[Plane],
+    (cam_trans, volume): (Transform, CullingVolume),
+    visible: &mut Vec<&'a Instance>,
+) {
+    let node = &bvh.nodes[node_index];
+    // The world space box, as the camera turns it out of line with the axes.
+    match node
+        .bounds
+        .to_obb()
+        .to_camera_space(cam_trans)
+        .classify(planes)
+    {
+        Containment::Outside => return,
+        Containment::Inside => {
+            visible.extend(bvh.instances_of(node_index).iter().map(|&i| &instances[i]));
+            return;
+        }
+        Containment::Intersecting => (),
+    }
+
+    match node.children {
+        Some(children) => {
+            for child in children {
+                clip_node(bvh, child, instances, planes, (cam_trans, volume), visible);
+            }
+        }
+        None => {
+            for &i in bvh.instances_of(node_index) {
+                let instance = &instances[i];
+                if instance_containment(instance, planes, cam_trans, volume) != Containment::Outside
+                {
+                    visible.push(instance);
+                }
+            }
         }
-        return None;
     }
-    Some(inst.clone())
 }
 
-fn clip_instance_against_plane(inst: &Instance, plane: &Plane) -> Option<Instance> {
-    let d = signed_dist(plane, inst.bounding_sphere.center);
-    let r = inst.bounding_sphere.radius;
+fn instance_containment(
+    instance: &Instance,
+    planes: &[Plane],
+    cam_trans: Transform,
+    volume: CullingVolume,
+) -> Containment {
+    let trans = instance.trans;
+    match volume {
+        CullingVolume::Sphere => instance
+            .bounding_sphere
+            .transformed(trans)
+            .to_camera_space(cam_trans)
+            .classify(planes),
+        // The world space box, as the camera turns it out of line with the axes.
+        CullingVolume::Aabb => instance
+            .aabb
+            .transformed(trans)
+            .to_obb()
+            .to_camera_space(cam_trans)
+            .classify(planes),
+        CullingVolume::Obb => instance
+            .obb
+            .transformed(trans)
+            .to_camera_space(cam_trans)
+            .classify(planes),
+    }
+}
 
-    if d > r {
-        return Some(inst.clone());
-    } else if d < -r {
-        return None;
-    } else {
-        return None;
-        /*
-        let mut clipped_inst = inst.clone();
-        clipped_inst.triangles = clip_tri_against_plane(&inst.triangles, plane, &inst.verts);
+/// The part of the camera space segment from `a` to `b` that's beyond the near plane.
+pub fn clip_segment_to_near(a: Vec3, b: Vec3) -> Option<(Vec3, Vec3)> {
+    match (a.z >= D, b.z >= D) {
+        (true, true) => Some((a, b)),
+        (true, false) => Some((a, near_crossing(a, b))),
+        (false, true) => Some((near_crossing(a, b), b)),
+        (false, false) => None,
+    }
+}
 
-        return Some(clipped_inst); */
+/// The part of the camera space triangle that's beyond the near plane, as up to two
+/// triangles wound the same way.
+pub fn clip_triangle_to_near(verts: [Vec3; 3]) -> Vec<[Vec3; 3]> {
+    let mut polygon = Vec::with_capacity(4);
+    for (i, &a) in verts.iter().enumerate() {
+        let b = verts[(i + 1) % 3];
+        if a.z >= D {
+            polygon.push(a);
+        }
+        if (a.z >= D) != (b.z >= D) {
+            polygon.push(near_crossing(a, b));
+        }
     }
+    (1..polygon.len().saturating_sub(1))
+        .map(|i| [polygon[0], polygon[i], polygon[i + 1]])
+        .collect()
+}
+
+/// Where the segment from `a` to `b` crosses the near plane.
+fn near_crossing(a: Vec3, b: Vec3) -> Vec3 {
+    a + (b - a) * ((D - a.z) / (b.z - a.z))
 }
 
 fn signed_dist(plane: &Plane, vert: Vec3) -> f64 {
-    let normal = plane.normal;
-    vert.x * normal.x + vert.y * normal.y + vert.z + normal.z + plane.d
+    plane.normal.dot(vert) + plane.d
 }
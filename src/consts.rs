@@ -1,9 +1,10 @@
 #![allow(dead_code)]
-use cgmath::{Matrix4, Vector2, Vector3};
+use cgmath::{Matrix4, Vector2, Vector3, Vector4};
 use pixels::wgpu::Color;
 
 pub type Vec2 = Vector2<f64>;
 pub type Vec3 = Vector3<f64>;
+pub type Vec4 = Vector4<f64>;
 pub type Mat4 = Matrix4<f64>;
 pub type Vertices = Vec<Vec3>;
 pub type Indices = (usize, usize, usize);
@@ -28,3 +29,35 @@ pub const WINDOW_SIZE: u32 = 720;
 pub const CANVAS_SIZE: u32 = 900;
 pub const VIEWPORT_SIZE: u32 = 1;
 pub const D: f64 = 1.;
+pub const DEFAULT_CREASE_ANGLE: f64 = 60.;
+pub const NORMAL_LINE_LENGTH: f64 = 0.3;
+/// Screen size in pixels of `RenderMode::Points` sprites without scene settings.
+pub const DEFAULT_POINT_SIZE: f64 = 3.;
+/// View depths between which wireframes fade out when depth cueing without scene settings.
+pub const DEPTH_CUE_START: f64 = 2.;
+pub const DEPTH_CUE_END: f64 = 20.;
+/// Length of the gizmo axes as a fraction of the view's height, whatever their distance.
+pub const GIZMO_SIZE: f64 = 0.15;
+/// How close to a gizmo handle in pixels a click has to be to grab it.
+pub const GIZMO_PICK_RADIUS: f64 = 8.;
+/// Most instances a BVH leaf holds.
+pub const BVH_LEAF_SIZE: usize = 4;
+/// How many times its built size the total area of a BVH's boxes may grow to through
+/// refitting before it's rebuilt.
+pub const BVH_REBUILD_GROWTH: f64 = 2.;
+pub const OUTLINE_WIDTH: i32 = 2;
+pub const OUTLINE_COLOR: [u8; 3] = [255, 160, 0];
+pub const OVERLAY_COLOR: [u8; 3] = [255, 220, 140];
+/// How far in front of the depth buffer, as a fraction of 1/z, overlay edges count.
+pub const OVERLAY_DEPTH_BIAS: f64 = 0.01;
+pub const HUD_TEXT_SCALE: i32 = 2;
+pub const HUD_MARGIN: i32 = 10;
+/// Weight of the newest frame in the HUD's averaged frame time.
+pub const HUD_SMOOTHING: f64 = 0.05;
+pub const RELOAD_POLL_INTERVAL: f64 = 0.5;
+/// Significant digits of the transforms written back into scene files.
+pub const SAVED_DIGITS: usize = 6;
+pub const SHADOW_MAP_SIZE: u32 = 512;
+pub const SHADOW_BIAS: f64 = 0.05;
+pub const SHADOW_NEAR: f64 = 0.1;
+pub const SHADOW_PCF_RADIUS: i32 = 1;
@@ -0,0 +1,85 @@
+use crate::put_pixel;
+
+/// Alternate ways to draw the scene while tuning it. They replace every material, and
+/// fog and lighting don't apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugView {
+    /// Regular rendering.
+    #[default]
+    Off,
+    /// Pixel writes that passed the depth test, from blue (once) to red (5 times or more).
+    Overdraw,
+    /// Depth buffer in grayscale, the nearest pixel white and the farthest black.
+    Depth,
+    /// Camera space normals, xyz mapped from -1..1 to RGB.
+    Normals,
+    /// Texture coordinates in red and green, wrapped to 0..1.
+    Uvs,
+    /// Every triangle in its own color.
+    TriangleIds,
+}
+impl DebugView {
+    pub const ALL: [DebugView; 6] = [
+        DebugView::Off,
+        DebugView::Overdraw,
+        DebugView::Depth,
+        DebugView::Normals,
+        DebugView::Uvs,
+        DebugView::TriangleIds,
+    ];
+
+    /// The view after this one, wrapping around to `Off`.
+    pub fn next(self) -> Self {
+        let i = DebugView::ALL.iter().position(|&v| v == self).unwrap();
+        DebugView::ALL[(i + 1) % DebugView::ALL.len()]
+    }
+
+    /// Turns what the views wrote during the frame into final colors. `Overdraw`
+    /// counts writes in the red channel, `Depth` only fills the depth buffer.
+    pub fn finish(self, frame: &mut [u8], depth_buffer: &[f64]) {
+        match self {
+            DebugView::Overdraw => {
+                for i in 0..depth_buffer.len() {
+                    let writes = frame[i * 4] as usize;
+                    if writes > 0 {
+                        put_pixel(frame, i, HEAT[writes.min(HEAT.len()) - 1]);
+                    }
+                }
+            }
+            DebugView::Depth => {
+                let depths = depth_buffer
+                    .iter()
+                    .filter(|&&inv_z| inv_z > 0.)
+                    .map(|d| 1. / d);
+                let (near, far) =
+                    depths.fold((f64::MAX, 0f64), |(near, far), z| (near.min(z), far.max(z)));
+                let range = (far - near).max(1e-9);
+                for (i, &inv_z) in depth_buffer.iter().enumerate() {
+                    if inv_z > 0. {
+                        let gray = (255. * (far - 1. / inv_z) / range) as u8;
+                        put_pixel(frame, i, [gray; 3]);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+const HEAT: [[u8; 3]; 5] = [
+    [0, 0, 255],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 255, 0],
+    [255, 0, 0],
+];
+
+/// A bright, stable pseudo random color for triangle `id`.
+pub fn id_color(id: usize) -> [u8; 3] {
+    // splitmix64 finalizer, so neighbouring ids get unrelated colors.
+    let mut x = (id as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    [x, x >> 8, x >> 16].map(|c| 64 + (c as u8) % 192)
+}
@@ -0,0 +1,94 @@
+use crate::{
+    consts::*,
+    instance::Transform,
+    material::{blend_colors, Texture},
+    put_pixel,
+};
+use cgmath::*;
+use std::rc::Rc;
+
+/// What's drawn where no geometry is, and what reflective materials mirror.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Solid([u8; 3]),
+    /// Blends from `bottom` straight down to `top` straight up.
+    Gradient {
+        top: [u8; 3],
+        bottom: [u8; 3],
+    },
+    Skybox(Rc<Cubemap>),
+}
+impl Background {
+    /// Color seen when looking along the world space direction `dir`.
+    pub fn sample(&self, dir: Vec3) -> [u8; 3] {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { top, bottom } => {
+                let up = dir.y / dir.magnitude();
+                blend_colors(*bottom, *top, 0.5 + 0.5 * up)
+            }
+            Background::Skybox(cubemap) => cubemap.sample(dir),
+        }
+    }
+
+    /// Fills the whole frame, looking up every pixel's view direction.
+    pub fn draw(&self, frame: &mut [u8], cam_trans: Transform) {
+        if let Background::Solid(color) = self {
+            for i in 0..(CANVAS_SIZE * CANVAS_SIZE) as usize {
+                put_pixel(frame, i, *color);
+            }
+            return;
+        }
+        let half = CANVAS_SIZE as f64 / 2.;
+        let pixel_size = VIEWPORT_SIZE as f64 / CANVAS_SIZE as f64;
+        for i in 0..(CANVAS_SIZE * CANVAS_SIZE) as usize {
+            let x = (i % CANVAS_SIZE as usize) as f64 - half;
+            let y = half - (i / CANVAS_SIZE as usize) as f64;
+            let view = Vec3::new(x * pixel_size, y * pixel_size, D);
+            put_pixel(frame, i, self.sample(cam_trans.rotate_from_camera(view)));
+        }
+    }
+}
+impl Default for Background {
+    fn default() -> Self {
+        Background::Solid([0, 0, 0])
+    }
+}
+
+/// Six square images around the viewer, in the order +x, -x, +y, -y, +z, -z. Faces
+/// are laid out like OpenGL cubemaps, as seen from the inside with y up (for the
+/// +y and -y faces, +z is up and down respectively).
+#[derive(Debug, Clone)]
+pub struct Cubemap {
+    pub faces: [Texture; 6],
+}
+impl Cubemap {
+    pub fn new(faces: [Texture; 6]) -> Self {
+        Self { faces }
+    }
+
+    pub fn sample(&self, dir: Vec3) -> [u8; 3] {
+        let abs = Vec3::new(dir.x.abs(), dir.y.abs(), dir.z.abs());
+        // Face, then the direction components running right and down the face image.
+        let (face, right, down, major) = if abs.x >= abs.y && abs.x >= abs.z {
+            if dir.x > 0. {
+                (0, -dir.z, -dir.y, abs.x)
+            } else {
+                (1, dir.z, -dir.y, abs.x)
+            }
+        } else if abs.y >= abs.z {
+            if dir.y > 0. {
+                (2, dir.x, dir.z, abs.y)
+            } else {
+                (3, dir.x, -dir.z, abs.y)
+            }
+        } else if dir.z > 0. {
+            (4, dir.x, -dir.y, abs.z)
+        } else {
+            (5, -dir.x, -dir.y, abs.z)
+        };
+        // Texture::sample wraps, so keep the far edges from wrapping to the other side.
+        let to_uv = |c: f64| (0.5 + 0.5 * c / major).clamp(0., 1. - 1e-9);
+        self.faces[face].sample(to_uv(right), to_uv(down))
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{consts::*, material::blend_colors, put_pixel};
+use serde::Deserialize;
+
+/// How quickly things disappear into the fog, measured by view depth (camera space z).
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum FogFalloff {
+    /// Clear up to `start`, fully fogged from `end` on.
+    Linear {
+        start: f64,
+        end: f64,
+    },
+    Exp {
+        density: f64,
+    },
+    Exp2 {
+        density: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Fog {
+    pub falloff: FogFalloff,
+    pub color: [u8; 3],
+}
+impl Fog {
+    pub fn new(falloff: FogFalloff, color: [u8; 3]) -> Self {
+        Self { falloff, color }
+    }
+
+    /// Fraction of a surface's own color still visible at `depth`, from 1 down to 0.
+    pub fn visibility(&self, depth: f64) -> f64 {
+        let visibility = match self.falloff {
+            FogFalloff::Linear { start, end } => (end - depth) / (end - start),
+            FogFalloff::Exp { density } => (-density * depth).exp(),
+            FogFalloff::Exp2 { density } => (-(density * depth).powi(2)).exp(),
+        };
+        visibility.clamp(0., 1.)
+    }
+
+    pub fn apply(&self, color: [u8; 3], depth: f64) -> [u8; 3] {
+        blend_colors(self.color, color, self.visibility(depth))
+    }
+
+    /// Fogs every pixel that has a depth, i.e. everything but the background and lines.
+    pub fn apply_to_frame(&self, frame: &mut [u8], depth_buffer: &[f64]) {
+        for (i, &inv_z) in depth_buffer.iter().enumerate() {
+            if inv_z <= 0. {
+                continue;
+            }
+            let color = [frame[i * 4], frame[i * 4 + 1], frame[i * 4 + 2]];
+            put_pixel(frame, i, self.apply(color, 1. / inv_z));
+        }
+    }
+}
+impl Default for Fog {
+    /// Fades into the black background, used for depth cueing when the scene sets none.
+    fn default() -> Self {
+        Fog::new(
+            FogFalloff::Linear {
+                start: DEPTH_CUE_START,
+                end: DEPTH_CUE_END,
+            },
+            [0, 0, 0],
+        )
+    }
+}
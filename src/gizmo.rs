@@ -0,0 +1,306 @@
+use crate::{
+    animation::wrap_degrees,
+    consts::*,
+    draw_line,
+    instance::{project_vertex, Transform},
+    picking::Ray,
+    scene_graph::SceneGraph,
+};
+use cgmath::*;
+
+const AXES: [Vec3; 3] = [
+    Vec3::new(1., 0., 0.),
+    Vec3::new(0., 1., 0.),
+    Vec3::new(0., 0., 1.),
+];
+const AXIS_COLORS: [[u8; 3]; 3] = [[230, 60, 60], [60, 200, 60], [60, 110, 240]];
+const ACTIVE_COLOR: [u8; 3] = [255, 220, 0];
+/// Plane handles span this part of the axes, as a fraction of their length.
+const PLANE_HANDLE: (f64, f64) = (0.3, 0.5);
+const RING_SEGMENTS: usize = 48;
+/// Half the width in pixels of the boxes at the tips of the scale handles.
+const SCALE_TIP_SIZE: f64 = 4.;
+/// Keeps scale handles dragged far back from flipping or flattening instances.
+const MIN_SCALE_FACTOR: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    /// Turns about the y axis, the only rotation a `Transform` has.
+    Rotate,
+    /// Scales uniformly, whichever axis is dragged.
+    Scale,
+}
+impl GizmoMode {
+    pub fn next(self) -> Self {
+        match self {
+            GizmoMode::Translate => GizmoMode::Rotate,
+            GizmoMode::Rotate => GizmoMode::Scale,
+            GizmoMode::Scale => GizmoMode::Translate,
+        }
+    }
+}
+
+/// Part of a gizmo that can be dragged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handle {
+    /// Along world axis 0, 1 or 2 for x, y and z.
+    Axis(usize),
+    /// In the plane of the two axes other than this one.
+    Plane(usize),
+    /// Around the y axis. The camera only turns about y, so the ring is often seen
+    /// edge on; sideways drags turn it as if pulling its front.
+    Ring,
+}
+
+/// A handle being dragged.
+struct Drag {
+    handle: Handle,
+    /// World space position of the gizmo when the drag started.
+    pivot: Vec3,
+    /// Length of the gizmo's axes and radius of its ring when the drag started.
+    length: f64,
+    cam_trans: Transform,
+    /// Where the cursor ray met the handle when the drag started.
+    grab: Vec3,
+    /// The nodes being edited and their rest transforms when the drag started. Edits
+    /// are applied to these, so errors don't build up while dragging.
+    nodes: Vec<(usize, Transform)>,
+}
+
+/// On-screen handles for moving, turning and scaling the selected instances. The
+/// gizmo sits on the last selected instance and its axes are the world axes.
+#[derive(Default)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    drag: Option<Drag>,
+}
+impl Gizmo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// The handle within `GIZMO_PICK_RADIUS` pixels of a canvas point, the nearest if
+    /// there are several.
+    pub fn handle_at(&self, pivot: Vec3, cam_trans: Transform, point: Vec2) -> Option<Handle> {
+        let mut nearest: Option<(Handle, f64)> = None;
+        for (handle, outline) in self.handles(pivot, cam_trans) {
+            let Some(outline) = project_outline(&outline, cam_trans) else {
+                continue;
+            };
+            let dist = if matches!(handle, Handle::Plane(_)) && inside_polygon(point, &outline) {
+                0.
+            } else {
+                outline
+                    .windows(2)
+                    .map(|edge| dist_to_segment(point, edge[0], edge[1]))
+                    .fold(f64::MAX, f64::min)
+            };
+            if dist <= GIZMO_PICK_RADIUS && nearest.is_none_or(|(_, nearest)| dist < nearest) {
+                nearest = Some((handle, dist));
+            }
+        }
+        nearest.map(|(handle, _)| handle)
+    }
+
+    /// Starts dragging `handle` with the cursor at `ray`, a world space ray. Instances
+    /// below another selected one follow it rather than being edited themselves.
+    pub fn begin_drag(
+        &mut self,
+        handle: Handle,
+        pivot: Vec3,
+        (ray, cam_trans): (Ray, Transform),
+        graph: &SceneGraph,
+        selection: &[usize],
+    ) {
+        let Some(grab) = drag_point(handle, pivot, ray, cam_trans) else {
+            return;
+        };
+        let selected: Vec<usize> = selection.iter().filter_map(|&i| graph.node_of(i)).collect();
+        let nodes = selected
+            .iter()
+            .filter(|&&node| !selected.iter().any(|&other| graph.is_below(node, other)))
+            .map(|&node| (node, graph.nodes[node].rest))
+            .collect();
+        self.drag = Some(Drag {
+            handle,
+            pivot,
+            length: gizmo_length(pivot, cam_trans),
+            cam_trans,
+            grab,
+            nodes,
+        });
+    }
+
+    /// Edits the rest transforms of the dragged nodes for the cursor now being at `ray`.
+    /// They show once `animation::animate` and `SceneGraph::update` have run.
+    pub fn drag(&self, ray: Ray, graph: &mut SceneGraph) {
+        let Some(drag) = &self.drag else {
+            return;
+        };
+        let Some(point) = drag_point(drag.handle, drag.pivot, ray, drag.cam_trans) else {
+            return;
+        };
+        for &(node, start) in &drag.nodes {
+            let parent = match graph.nodes[node].parent {
+                Some(parent) => graph.world_transform(parent),
+                None => Transform::new(Vec3::zero(), 1.),
+            };
+            let mut rest = start;
+            match self.mode {
+                GizmoMode::Translate => {
+                    let world = parent.to_parent_space(start.translation) + point - drag.grab;
+                    rest.translation = parent.inverse().to_parent_space(world);
+                }
+                GizmoMode::Rotate => {
+                    // Positive turns take +x towards -z, which moves the front of the
+                    // ring to the left.
+                    let right = drag.cam_trans.rotate_from_camera(AXES[0]);
+                    let arc = (point - drag.grab).dot(right);
+                    let turn = -(arc / drag.length).to_degrees();
+                    rest.rot = start.rot + wrap_degrees(turn);
+                }
+                GizmoMode::Scale => {
+                    let Handle::Axis(axis) = drag.handle else {
+                        continue;
+                    };
+                    let along = |p: Vec3| (p - drag.pivot).dot(AXES[axis]);
+                    // Dragging a full axis length doubles the scale. Measuring from
+                    // the grab rather than the pivot keeps grabs near it from exploding.
+                    let factor = 1. + (along(point) - along(drag.grab)) / drag.length;
+                    rest.scale = start.scale * factor.max(MIN_SCALE_FACTOR);
+                }
+            }
+            graph.nodes[node].rest = rest;
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    /// The handles of the current mode with their outlines in world space. The gizmo
+    /// keeps the same size on screen wherever it is.
+    fn handles(&self, pivot: Vec3, cam_trans: Transform) -> Vec<(Handle, Vec<Vec3>)> {
+        let length = gizmo_length(pivot, cam_trans);
+        match self.mode {
+            GizmoMode::Translate => {
+                let mut handles: Vec<(Handle, Vec<Vec3>)> = (0..3)
+                    .map(|axis| (Handle::Axis(axis), axis_outline(pivot, axis, length)))
+                    .collect();
+                for axis in 0..3 {
+                    let (u, v) = (AXES[(axis + 1) % 3], AXES[(axis + 2) % 3]);
+                    let (near, far) = (PLANE_HANDLE.0 * length, PLANE_HANDLE.1 * length);
+                    let corners = [
+                        (near, near),
+                        (far, near),
+                        (far, far),
+                        (near, far),
+                        (near, near),
+                    ];
+                    let outline = corners.iter().map(|&(a, b)| pivot + u * a + v * b);
+                    handles.push((Handle::Plane(axis), outline.collect()));
+                }
+                handles
+            }
+            GizmoMode::Rotate => {
+                let ring = (0..=RING_SEGMENTS).map(|i| {
+                    let angle = i as f64 / RING_SEGMENTS as f64 * std::f64::consts::TAU;
+                    pivot + Vec3::new(angle.cos(), 0., angle.sin()) * length
+                });
+                vec![(Handle::Ring, ring.collect())]
+            }
+            GizmoMode::Scale => (0..3)
+                .map(|axis| (Handle::Axis(axis), axis_outline(pivot, axis, length)))
+                .collect(),
+        }
+    }
+
+    /// Draws the handles of the current mode around `pivot`, the dragged one or else
+    /// `hovered` highlighted.
+    pub fn draw(
+        &self,
+        frame: &mut [u8],
+        pivot: Vec3,
+        cam_trans: Transform,
+        hovered: Option<Handle>,
+    ) {
+        let active = self.drag.as_ref().map(|drag| drag.handle).or(hovered);
+        for (handle, outline) in self.handles(pivot, cam_trans) {
+            let Some(outline) = project_outline(&outline, cam_trans) else {
+                continue;
+            };
+            let color = match handle {
+                _ if Some(handle) == active => ACTIVE_COLOR,
+                Handle::Axis(axis) | Handle::Plane(axis) => AXIS_COLORS[axis],
+                Handle::Ring => AXIS_COLORS[1],
+            };
+            for edge in outline.windows(2) {
+                draw_line(edge[0], edge[1], frame, color);
+            }
+            if let (GizmoMode::Scale, Some(&tip)) = (self.mode, outline.last()) {
+                let s = SCALE_TIP_SIZE;
+                let corners = [(-s, -s), (s, -s), (s, s), (-s, s), (-s, -s)]
+                    .map(|(x, y)| tip + Vec2::new(x, y));
+                for edge in corners.windows(2) {
+                    draw_line(edge[0], edge[1], frame, color);
+                }
+            }
+        }
+    }
+}
+
+/// Where a world space ray meets the surface a handle moves along.
+fn drag_point(handle: Handle, pivot: Vec3, ray: Ray, cam_trans: Transform) -> Option<Vec3> {
+    match handle {
+        Handle::Axis(axis) => ray.closest_on_line(pivot, AXES[axis]),
+        Handle::Plane(axis) => ray.hit_plane(pivot, AXES[axis]),
+        Handle::Ring => ray.hit_plane(pivot, cam_trans.rotate_from_camera(AXES[2])),
+    }
+}
+
+/// World space length that makes the gizmo axes `GIZMO_SIZE` of the view's height.
+fn gizmo_length(pivot: Vec3, cam_trans: Transform) -> f64 {
+    GIZMO_SIZE * VIEWPORT_SIZE as f64 * cam_trans.to_camera_space(pivot).z / D
+}
+
+fn axis_outline(pivot: Vec3, axis: usize, length: f64) -> Vec<Vec3> {
+    vec![pivot, pivot + AXES[axis] * length]
+}
+
+/// Canvas positions of a world space outline, `None` if any of it is in front of the
+/// near plane.
+fn project_outline(outline: &[Vec3], cam_trans: Transform) -> Option<Vec<Vec2>> {
+    outline
+        .iter()
+        .map(|&p| {
+            let p = cam_trans.to_camera_space(p);
+            (p.z >= D).then(|| project_vertex(p))
+        })
+        .collect()
+}
+
+fn dist_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f64 {
+    let ab = b - a;
+    let len2 = ab.magnitude2();
+    if len2 == 0. {
+        return p.distance(a);
+    }
+    let t = ((p - a).dot(ab) / len2).clamp(0., 1.);
+    p.distance(a + ab * t)
+}
+
+/// Whether `p` is inside a convex polygon given as a closed outline.
+fn inside_polygon(p: Vec2, outline: &[Vec2]) -> bool {
+    let sides = outline.windows(2).map(|edge| {
+        let (edge, to_p) = (edge[1] - edge[0], p - edge[0]);
+        edge.perp_dot(to_p)
+    });
+    let sides: Vec<f64> = sides.collect();
+    sides.iter().all(|&side| side >= 0.) || sides.iter().all(|&side| side <= 0.)
+}
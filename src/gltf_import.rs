@@ -0,0 +1,662 @@
+use crate::{
+    animation::{Animation, Clock, Interpolation, Track},
+    clipping::BoundingSphere,
+    consts::*,
+    environment::Background,
+    instance::{Instance, Transform},
+    light::Light,
+    material::{Material, RenderMode, Texture},
+    mesh::{normalize_or_zero, Mesh, MeshError},
+    scene::{Scene, SceneError},
+    scene_graph::SceneGraph,
+};
+use cgmath::*;
+use gltf::{animation::util::ReadOutputs, buffer, image, khr_lights_punctual::Kind, mesh::Mode};
+use log::warn;
+use std::{collections::HashMap, ops::Range, path::Path, rc::Rc};
+
+/// Loads a .gltf or .glb file as a scene: the default scene's node hierarchy with its
+/// meshes, materials, lights and first camera, and the file's first animation.
+///
+/// `Transform` only turns about y and scales uniformly, so every node keeps the turn
+/// about y of its rotation and the average of its scale. Whatever else the node's
+/// transform does gets baked into its mesh, which places everything exactly where the
+/// file has it, but animations only carry over translation, the turn about y and
+/// uniform scale. Skins, morph targets and the camera's field of view are ignored.
+///
+/// glTF is right handed with +z towards the viewer, here +z points into the screen, so
+/// everything gets mirrored along z on the way in. That keeps text on textures reading
+/// the right way round and puts the front of a model towards the camera.
+pub fn load_gltf(path: &Path) -> Result<Scene, SceneError> {
+    let (document, buffers, images) = gltf::import(path).map_err(|error| SceneError::Gltf {
+        path: path.into(),
+        error,
+    })?;
+
+    let mut importer = Importer {
+        path,
+        buffers: &buffers,
+        images: &images,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        instances: Vec::new(),
+        graph: SceneGraph::default(),
+        nodes: HashMap::new(),
+        lights: Vec::new(),
+        camera: None,
+    };
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes() {
+            importer.add(
+                &node,
+                None,
+                Mat4::identity(),
+                Transform::new(Vec3::zero(), 1.),
+            )?;
+        }
+    }
+
+    let mut animations = Vec::new();
+    let mut gltf_animations = document.animations();
+    if let Some(gltf_animation) = gltf_animations.next() {
+        animations = importer.animation(&gltf_animation);
+    }
+    if gltf_animations.next().is_some() {
+        warn!("{}: only the first animation is played", path.display());
+    }
+
+    let Importer {
+        mut instances,
+        graph,
+        lights,
+        camera,
+        ..
+    } = importer;
+    graph.update(&mut instances);
+    let camera = camera.unwrap_or_else(|| framing_camera(&instances));
+
+    Ok(Scene {
+        instances,
+        graph,
+        animations,
+        clock: Clock::default(),
+        lights: balance_lights(lights),
+        camera,
+        background: Background::default(),
+        fog: None,
+        depth_cue: None,
+        mesh_files: HashMap::new(),
+    })
+}
+
+struct Importer<'a> {
+    path: &'a Path,
+    buffers: &'a [buffer::Data],
+    images: &'a [image::Data],
+    /// By image index.
+    textures: HashMap<usize, Option<Rc<Texture>>>,
+    /// By material index, `None` being the default material.
+    materials: HashMap<Option<usize>, Material>,
+    instances: Vec<Instance>,
+    graph: SceneGraph,
+    /// Scene graph node and what's left of the parent's transform, by glTF node index.
+    nodes: HashMap<usize, (usize, Mat4)>,
+    lights: Vec<Light>,
+    camera: Option<Transform>,
+}
+impl Importer<'_> {
+    /// `parent_world` is the parent's exact world matrix, `parent_approx` the world
+    /// transform it got in the scene graph.
+    fn add(
+        &mut self,
+        node: &gltf::Node,
+        parent: Option<usize>,
+        parent_world: Mat4,
+        parent_approx: Transform,
+    ) -> Result<(), SceneError> {
+        let world = parent_world * mirror_matrix(to_mat4(node.transform().matrix()));
+        // Takes points in the parent's space of the file to the parent's graph space.
+        let parent_rest = invert_or_identity(parent_approx.matrix()) * parent_world;
+
+        let (translation, rotation, scale) = node.transform().decomposed();
+        let local = Transform {
+            translation: transform_point(parent_rest, mirror(translation)),
+            scale: (scale[0] + scale[1] + scale[2]) as f64 / 3.,
+            rot: yaw(rotation),
+        };
+        let approx = parent_approx.combine(local);
+        let rest = invert_or_identity(approx.matrix()) * world;
+
+        let mut instance = None;
+        if let Some(mesh) = node.mesh() {
+            if let Some(mesh_instance) = self.instance(&mesh, rest, local)? {
+                self.instances.push(mesh_instance);
+                instance = Some(self.instances.len() - 1);
+            }
+        }
+        let graph_node = self.graph.add_node(parent, local, instance);
+        self.graph.nodes[graph_node].name = node.name().map(str::to_string);
+        self.nodes.insert(node.index(), (graph_node, parent_rest));
+
+        if node.camera().is_some() && self.camera.is_none() {
+            self.camera = Some(camera_at(world));
+        }
+        if let Some(light) = node.light() {
+            self.lights.push(light_at(&light, world));
+        }
+
+        for child in node.children() {
+            self.add(&child, Some(graph_node), world, approx)?;
+        }
+        Ok(())
+    }
+
+    /// Joins the primitives into one mesh, moved by `rest`, with a material range each.
+    /// `None` for meshes without triangles.
+    fn instance(
+        &mut self,
+        mesh: &gltf::Mesh,
+        rest: Mat4,
+        local: Transform,
+    ) -> Result<Option<Instance>, SceneError> {
+        let invalid = |msg: String| SceneError::Mesh {
+            name: mesh
+                .name()
+                .map_or_else(|| format!("#{}", mesh.index()), str::to_string),
+            path: self.path.into(),
+            error: MeshError::Invalid(msg),
+        };
+        let mut verts = Vec::new();
+        let mut normals = Some(Vec::new());
+        let mut uvs = Some(Vec::new());
+        let mut triangles = Vec::new();
+        let mut ranges: Vec<(Range<usize>, gltf::Material)> = Vec::new();
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                warn!("skipping a primitive of mode {:?}", primitive.mode());
+                continue;
+            }
+            let reader = primitive.reader(|b| self.buffers.get(b.index()).map(|data| &data.0[..]));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let first_vert = verts.len();
+            verts.extend(positions.map(mirror));
+            let vert_count = verts.len() - first_vert;
+
+            match (&mut normals, reader.read_normals()) {
+                (Some(normals), Some(read)) => {
+                    normals.extend(read.map(mirror));
+                    if normals.len() != verts.len() {
+                        return Err(invalid(format!(
+                            "{} positions but {} normals",
+                            vert_count,
+                            normals.len() - first_vert
+                        )));
+                    }
+                }
+                _ => normals = None,
+            }
+            // Textures say which uv set they use, the mesh keeps one so it's the color
+            // texture's.
+            let pbr = primitive.material().pbr_metallic_roughness();
+            let tex_coord = pbr
+                .base_color_texture()
+                .map(|info| info.tex_coord())
+                .or_else(|| {
+                    primitive
+                        .material()
+                        .normal_texture()
+                        .map(|info| info.tex_coord())
+                })
+                .unwrap_or(0);
+            match (&mut uvs, reader.read_tex_coords(tex_coord)) {
+                (Some(uvs), Some(read)) => {
+                    uvs.extend(read.into_f32().map(|[u, v]| Vec2::new(u as f64, v as f64)));
+                    if uvs.len() != verts.len() {
+                        return Err(invalid(format!(
+                            "{} positions but {} uvs",
+                            vert_count,
+                            uvs.len() - first_vert
+                        )));
+                    }
+                }
+                _ => uvs = None,
+            }
+
+            let first_tri = triangles.len();
+            let indices: Vec<usize> = match reader.read_indices() {
+                Some(read) => read.into_u32().map(|i| i as usize).collect(),
+                None => (0..vert_count).collect(),
+            };
+            if !indices.len().is_multiple_of(3) {
+                return Err(invalid("the index count isn't a multiple of 3".to_string()));
+            }
+            if let Some(&index) = indices.iter().find(|&&i| i >= vert_count) {
+                return Err(invalid(format!(
+                    "index {} is past the {} vertices",
+                    index, vert_count
+                )));
+            }
+            // Mirroring turns the triangles around, swapping two corners turns them back.
+            for tri in indices.chunks_exact(3) {
+                triangles.push((
+                    first_vert + tri[0],
+                    first_vert + tri[2],
+                    first_vert + tri[1],
+                ));
+            }
+            ranges.push((first_tri..triangles.len(), primitive.material()));
+        }
+        if triangles.is_empty() {
+            return Ok(None);
+        }
+
+        let normal_matrix =
+            Matrix3::from_cols(rest.x.truncate(), rest.y.truncate(), rest.z.truncate())
+                .invert()
+                .map(|m| m.transpose())
+                .unwrap_or_else(Matrix3::identity);
+        let verts = verts.iter().map(|&v| transform_point(rest, v)).collect();
+        if rest.determinant() < 0. {
+            // Mirrored, so the triangles turn around.
+            for tri in &mut triangles {
+                *tri = (tri.0, tri.2, tri.1);
+            }
+        }
+        let mesh = match normals {
+            Some(normals) => {
+                let normals = normals
+                    .iter()
+                    .map(|&n| normalize_or_zero(normal_matrix * n))
+                    .collect();
+                Mesh::with_normals(verts, triangles, normals, uvs)
+            }
+            None => Mesh::new(verts, triangles, uvs),
+        };
+
+        let mut ranges = ranges.into_iter();
+        let Some((_, first_material)) = ranges.next() else {
+            return Ok(None);
+        };
+        let material = self.material(&first_material);
+        let mut instance =
+            Instance::from_mesh(Rc::new(mesh), local.translation, local.scale, material);
+        for (tris, material) in ranges {
+            let material = self.material(&material);
+            instance.set_material_range(tris, material);
+        }
+        Ok(Some(instance))
+    }
+
+    fn material(&mut self, gltf_material: &gltf::Material) -> Material {
+        if let Some(material) = self.materials.get(&gltf_material.index()) {
+            return material.clone();
+        }
+        let material = self.build_material(gltf_material);
+        self.materials
+            .insert(gltf_material.index(), material.clone());
+        material
+    }
+
+    /// The default material has no index and comes out plain white.
+    fn build_material(&mut self, gltf_material: &gltf::Material) -> Material {
+        let pbr = gltf_material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let roughness = pbr.roughness_factor() as f64;
+
+        let mut material = Material::new([r, g, b].map(to_srgb), RenderMode::Phong)
+            .with_specular(specular_exponent(roughness))
+            .with_reflectivity(pbr.metallic_factor() as f64 * (1. - roughness));
+        if let Some(texture) = pbr
+            .base_color_texture()
+            .and_then(|info| self.texture(info.texture().source().index()))
+        {
+            material = material.with_texture(texture);
+        }
+        if let Some(normal_map) = gltf_material
+            .normal_texture()
+            .and_then(|info| self.texture(info.texture().source().index()))
+        {
+            material = material.with_normal_map(normal_map);
+        }
+        material
+    }
+
+    fn texture(&mut self, image: usize) -> Option<Rc<Texture>> {
+        let images = self.images;
+        self.textures
+            .entry(image)
+            .or_insert_with(|| to_texture(&images[image]).map(Rc::new))
+            .clone()
+    }
+
+    /// Gathers the channels of `animation` per node.
+    fn animation(&self, animation: &gltf::Animation) -> Vec<Animation> {
+        let mut animations: Vec<Animation> = Vec::new();
+        for channel in animation.channels() {
+            let Some(&(node, parent_rest)) = self.nodes.get(&channel.target().node().index())
+            else {
+                continue;
+            };
+            let reader = channel.reader(|b| self.buffers.get(b.index()).map(|data| &data.0[..]));
+            let (Some(inputs), Some(outputs)) = (reader.read_inputs(), reader.read_outputs())
+            else {
+                continue;
+            };
+            let times: Vec<f64> = inputs.map(|t| t as f64).collect();
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::Cubic,
+            };
+            // Cubic splines store (in tangent, value, out tangent) per key, the tangents
+            // get dropped for a Catmull-Rom spline through the values.
+            let keep = |i: usize| interpolation != Interpolation::Cubic || i % 3 == 1;
+
+            let index = match animations.iter().position(|a| a.node == node) {
+                Some(index) => index,
+                None => {
+                    animations.push(Animation {
+                        node,
+                        ..Animation::default()
+                    });
+                    animations.len() - 1
+                }
+            };
+            let target = &mut animations[index];
+            match outputs {
+                ReadOutputs::Translations(values) => {
+                    let values = values
+                        .enumerate()
+                        .filter(|&(i, _)| keep(i))
+                        .map(|(_, t)| transform_point(parent_rest, mirror(t)));
+                    target.translation = Track::new(interpolation, zip_keys(&times, values));
+                }
+                ReadOutputs::Rotations(values) => {
+                    let values = values
+                        .into_f32()
+                        .enumerate()
+                        .filter(|&(i, _)| keep(i))
+                        .map(|(_, q)| yaw(q));
+                    target.rotation = Track::new(interpolation, zip_keys(&times, values));
+                }
+                ReadOutputs::Scales(values) => {
+                    let values = values
+                        .enumerate()
+                        .filter(|&(i, _)| keep(i))
+                        .map(|(_, [x, y, z])| (x + y + z) as f64 / 3.);
+                    target.scale = Track::new(interpolation, zip_keys(&times, values));
+                }
+                ReadOutputs::MorphTargetWeights(_) => (),
+            }
+        }
+        animations
+    }
+}
+
+fn zip_keys<T>(times: &[f64], values: impl Iterator<Item = T>) -> Vec<(f64, T)> {
+    times.iter().copied().zip(values).collect()
+}
+
+/// glTF cameras look down their -z axis, which is +z once mirrored.
+fn camera_at(world: Mat4) -> Transform {
+    let position = transform_point(world, Vec3::zero());
+    let forward = (world * Vec4::new(0., 0., 1., 0.)).truncate();
+    let mut camera = Transform::new(-position - DEFAULT_TRANSL, 1.);
+    camera.rot = -forward.x.atan2(forward.z).to_degrees();
+    camera
+}
+
+/// Backs the camera off along z until the whole scene fits in view.
+fn framing_camera(instances: &[Instance]) -> Transform {
+    let bounds = instances
+        .iter()
+        .map(|instance| instance.bounding_sphere.transformed(instance.trans))
+        .reduce(BoundingSphere::merge);
+    let Some(bounds) = bounds else {
+        return Transform::new(Vec3::zero(), 1.);
+    };
+    let half_fov = (VIEWPORT_SIZE as f64 / 2.).atan2(D);
+    let distance = bounds.radius / half_fov.sin() + D;
+    let position = bounds.center - Vec3::new(0., 0., distance);
+    Transform::new(-position - DEFAULT_TRANSL, 1.)
+}
+
+/// Punctual lights shine down their -z axis, +z once mirrored. Shadows are left off,
+/// the file has no say in them.
+fn light_at(light: &gltf::khr_lights_punctual::Light, world: Mat4) -> Light {
+    let intensity = light.intensity() as f64;
+    let position = transform_point(world, Vec3::zero());
+    let direction = normalize_or_zero((world * Vec4::new(0., 0., 1., 0.)).truncate());
+    match light.kind() {
+        Kind::Directional => Light::Directional {
+            intensity,
+            direction,
+            shadows: false,
+        },
+        Kind::Point => Light::Point {
+            intensity,
+            position,
+        },
+        Kind::Spot {
+            outer_cone_angle, ..
+        } => Light::Spot {
+            intensity,
+            position,
+            direction,
+            angle: (outer_cone_angle as f64).to_degrees(),
+            shadows: false,
+        },
+    }
+}
+
+/// glTF intensities are physical units, ours fractions of full brightness. The lights
+/// keep their ratios and get scaled to add up to what the default scene uses, plus
+/// its ambient light. Without lights the default scene's lights are used.
+fn balance_lights(mut lights: Vec<Light>) -> Vec<Light> {
+    let total: f64 = lights.iter_mut().map(|light| *intensity(light)).sum();
+    if total <= 0. {
+        return Scene::default().lights;
+    }
+    let ambient = 0.2;
+    for light in &mut lights {
+        *intensity(light) *= (1. - ambient) / total;
+    }
+    lights.insert(0, Light::Ambient { intensity: ambient });
+    lights
+}
+
+fn intensity(light: &mut Light) -> &mut f64 {
+    match light {
+        Light::Ambient { intensity }
+        | Light::Point { intensity, .. }
+        | Light::Directional { intensity, .. }
+        | Light::Spot { intensity, .. } => intensity,
+    }
+}
+
+/// Only 8 bit images are supported; their alpha is dropped.
+fn to_texture(image: &image::Data) -> Option<Texture> {
+    let channels = match image.format {
+        image::Format::R8 => 1,
+        image::Format::R8G8 => 2,
+        image::Format::R8G8B8 => 3,
+        image::Format::R8G8B8A8 => 4,
+        format => {
+            warn!("skipping a texture of format {:?}", format);
+            return None;
+        }
+    };
+    let texels = image
+        .pixels
+        .chunks_exact(channels)
+        .map(|texel| match *texel {
+            [gray] => [gray; 3],
+            [r, g] => [r, g, 0],
+            [r, g, b, ..] => [r, g, b],
+            [] => unreachable!(),
+        })
+        .collect();
+    Some(Texture::new(
+        image.width as usize,
+        image.height as usize,
+        texels,
+    ))
+}
+
+/// Turn about y of a glTF rotation quaternion in `[x, y, z, w]` order, in degrees.
+fn yaw([x, y, z, w]: [f32; 4]) -> f64 {
+    // Mirrored along z, the rotation axis flips its x and y and the angle its sign.
+    let q = Quaternion::new(w as f64, -x as f64, -y as f64, z as f64);
+    let forward = q.rotate_vector(Vec3::unit_z());
+    if forward.x.abs() + forward.z.abs() > 1e-6 {
+        forward.x.atan2(forward.z).to_degrees()
+    } else {
+        // Looking straight up or down, follow the x axis instead.
+        let right = q.rotate_vector(Vec3::unit_x());
+        (-right.z).atan2(right.x).to_degrees()
+    }
+}
+
+/// Blinn-Phong exponent roughly matching a GGX roughness, -1 (matte) when fully rough.
+fn specular_exponent(roughness: f64) -> f64 {
+    if roughness >= 1. {
+        return -1.;
+    }
+    let alpha = (roughness * roughness).max(1e-3);
+    (2. / (alpha * alpha) - 2.).min(1000.)
+}
+
+/// glTF color factors are linear.
+fn to_srgb(linear: f32) -> u8 {
+    (linear.clamp(0., 1.).powf(1. / 2.2) * 255.).round() as u8
+}
+
+/// A glTF position or direction, mirrored along z.
+fn mirror([x, y, z]: [f32; 3]) -> Vec3 {
+    Vec3::new(x as f64, y as f64, -z as f64)
+}
+
+/// A glTF transform matrix working on mirrored points.
+fn mirror_matrix(m: Mat4) -> Mat4 {
+    let flip = Mat4::from_nonuniform_scale(1., 1., -1.);
+    flip * m * flip
+}
+
+fn to_mat4(m: [[f32; 4]; 4]) -> Mat4 {
+    let col = |c: [f32; 4]| Vec4::new(c[0] as f64, c[1] as f64, c[2] as f64, c[3] as f64);
+    Mat4::from_cols(col(m[0]), col(m[1]), col(m[2]), col(m[3]))
+}
+
+fn transform_point(m: Mat4, p: Vec3) -> Vec3 {
+    (m * p.extend(1.)).truncate()
+}
+
+fn invert_or_identity(m: Mat4) -> Mat4 {
+    m.invert().unwrap_or_else(Mat4::identity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Writes a glTF file with a triangle leaning towards the viewer, drawn by a node
+    /// moved towards the viewer and by one turned a quarter about y.
+    fn write_gltf(name: &str, indices: &[u16]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("gltf_import_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let s = 0.5f32.sqrt();
+        let positions = [[0., 0., 0.], [1., 0., 0.], [0., 1., 1.]];
+        let normals = [[0., -s, s]; 3];
+        let mut bin: Vec<u8> = positions
+            .iter()
+            .chain(&normals)
+            .flatten()
+            .flat_map(|c: &f32| c.to_le_bytes())
+            .collect();
+        bin.extend(indices.iter().flat_map(|i| i.to_le_bytes()));
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+        fs::write(dir.join(format!("{}.bin", name)), &bin).unwrap();
+        let json = format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "scene": 0,
+                "scenes": [{{"nodes": [0, 1]}}],
+                "nodes": [
+                    {{"name": "moved", "mesh": 0, "translation": [0, 0, 3]}},
+                    {{"name": "turned", "mesh": 0, "rotation": [0, {s}, 0, {s}]}}
+                ],
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0, "NORMAL": 1}}, "indices": 2}}]}}],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 1]}},
+                    {{"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3"}},
+                    {{"bufferView": 2, "componentType": 5123, "count": {count}, "type": "SCALAR"}}
+                ],
+                "bufferViews": [
+                    {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 36, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 72, "byteLength": {index_bytes}}}
+                ],
+                "buffers": [{{"byteLength": {len}, "uri": "{name}.bin"}}]
+            }}"#,
+            s = s,
+            count = indices.len(),
+            index_bytes = indices.len() * 2,
+            len = bin.len(),
+            name = name,
+        );
+        let path = dir.join(format!("{}.gltf", name));
+        fs::write(&path, json).unwrap();
+        path
+    }
+
+    fn world_verts(scene: &Scene, node: &str) -> Vertices {
+        let node = &scene.graph.nodes[scene.graph.find(node).unwrap()];
+        let instance = &scene.instances[node.instance.unwrap()];
+        let verts = instance.mesh.verts.iter();
+        verts.map(|&v| instance.trans.to_parent_space(v)).collect()
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn mirrors_into_left_handed_space() {
+        let scene = load_gltf(&write_gltf("mirror", &[0, 1, 2])).unwrap();
+
+        // Towards the viewer is -z here.
+        let moved = world_verts(&scene, "moved");
+        assert_near(moved[1], Vec3::new(1., 0., -3.));
+        assert_near(moved[2], Vec3::new(0., 1., -4.));
+        // A quarter turn about y takes +x to -z in glTF, so to +z here.
+        let turned = world_verts(&scene, "turned");
+        assert_near(turned[1], Vec3::new(0., 0., 1.));
+        assert_near(turned[2], Vec3::new(1., 1., 0.));
+
+        // The triangle and its normals still face the viewer.
+        let instance = &scene.instances[0];
+        let &(a, b, c) = &instance.mesh.triangles[0];
+        let v = |i: usize| instance.mesh.verts[i];
+        assert!((v(b) - v(a)).cross(v(c) - v(a)).z < 0.);
+        assert!(instance.mesh.normals.iter().all(|n| n.z < 0.));
+    }
+
+    #[test]
+    fn rejects_partial_triangles() {
+        let Err(error) = load_gltf(&write_gltf("partial", &[0, 1, 2, 0])) else {
+            panic!("a partial triangle got loaded");
+        };
+        assert!(error
+            .to_string()
+            .contains("the index count isn't a multiple of 3"));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{
+    clipping::CullingVolume, consts::*, debug_view::DebugView, gizmo::GizmoMode,
+    instance::Transform, stats::RenderStats, text::draw_text_shadowed,
+};
+
+/// On-screen overlay with frame timing and what's being rendered.
+pub struct Hud {
+    pub visible: bool,
+    /// Exponential moving average of the frame time in seconds, so the numbers don't
+    /// flicker every frame.
+    frame_time: f64,
+}
+impl Hud {
+    pub fn new() -> Self {
+        Self {
+            visible: true,
+            frame_time: 0.,
+        }
+    }
+
+    pub fn record_frame(&mut self, seconds: f64) {
+        if self.frame_time == 0. {
+            self.frame_time = seconds;
+        } else {
+            self.frame_time += (seconds - self.frame_time) * HUD_SMOOTHING;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &self,
+        frame: &mut [u8],
+        stats: &RenderStats,
+        cam_trans: Transform,
+        selected: usize,
+        gizmo: GizmoMode,
+        culling: CullingVolume,
+        debug_view: DebugView,
+    ) {
+        if !self.visible {
+            return;
+        }
+        let fps = if self.frame_time > 0. {
+            1. / self.frame_time
+        } else {
+            0.
+        };
+        // The camera moves the world by its translation, so it sits at the opposite.
+        let position = -(cam_trans.translation + DEFAULT_TRANSL);
+        let text = format!(
+            "FPS: {:.0}\nFrame: {:.1} ms\nInstances: {} / {}, culled by {:?} (B)\nTriangles: {} / {}\nOverdraw: {:.2}\nCamera: ({:.1}, {:.1}, {:.1}) {:.0} deg\nSelected: {}\nGizmo: {:?} (G)\nView: {:?} (V)",
+            fps,
+            self.frame_time * 1000.,
+            stats.instances_submitted - stats.instances_culled,
+            stats.instances_submitted,
+            culling,
+            stats.triangles_rasterized,
+            stats.triangles_submitted,
+            stats.overdraw(),
+            position.x,
+            position.y,
+            position.z,
+            cam_trans.rot,
+            selected,
+            gizmo,
+            debug_view,
+        );
+        draw_text_shadowed(frame, &text, HUD_MARGIN, HUD_MARGIN, HUD_TEXT_SCALE, WHITE);
+    }
+}
+impl Default for Hud {
+    fn default() -> Self {
+        Hud::new()
+    }
+}
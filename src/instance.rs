@@ -1,50 +1,500 @@
-use crate::{clipping::BoundingSphere, consts::*, draw_triangle, draw_wireframe_triangle};
+use crate::{
+    clipping::{clip_segment_to_near, Aabb, BoundingSphere, Obb},
+    consts::*,
+    debug_view::{id_color, DebugView},
+    draw_line, draw_point, draw_shaded_line, draw_triangle,
+    fog::Fog,
+    light::Lighting,
+    material::{blend_colors, shade_color, Material, MaterialRange, RenderMode},
+    mesh::Mesh,
+    primitives, put_pixel, rasterize,
+    shader::{CustomShader, Uniforms},
+    skeleton::{PosedMesh, Skin},
+    stats::RenderStats,
+};
 use cgmath::*;
+use serde::Deserialize;
+use std::{ops::Range, rc::Rc, time::Instant};
 
 #[derive(Debug, Clone)]
 pub struct Instance {
-    model: Model,
-    verts: Vertices,
-    triangles: Vec<Indices>,
+    pub mesh: Rc<Mesh>,
+    pub material: Material,
+    pub sub_materials: Vec<MaterialRange>,
     pub bounding_sphere: BoundingSphere,
+    pub aabb: Aabb,
+    pub obb: Obb,
     pub trans: Transform,
+    /// Deforms the mesh, see `pose_skin`.
+    pub skin: Option<Skin>,
+    /// The skinned mesh in the skin's current pose.
+    posed: Option<PosedMesh>,
 }
 impl Instance {
-    pub fn new(model: Model, translation: Vec3, scale: f64) -> Self {
+    pub fn new(model: Model, translation: Vec3, scale: f64, material: Material) -> Self {
+        Instance::from_mesh(Rc::new(model.get_mesh()), translation, scale, material)
+    }
+
+    pub fn from_mesh(mesh: Rc<Mesh>, translation: Vec3, scale: f64, material: Material) -> Self {
         let trans = Transform::new(translation, scale);
-        let verts = model.get_verts();
-        let bounding_sphere = BoundingSphere::new(&verts);
         Self {
-            model,
-            verts,
-            triangles: model.get_indices(),
-            bounding_sphere,
+            bounding_sphere: BoundingSphere::new(&mesh.verts),
+            aabb: Aabb::new(&mesh.verts),
+            obb: Obb::new(&mesh.verts),
+            mesh,
+            material,
+            sub_materials: Vec::new(),
             trans,
+            skin: None,
+            posed: None,
+        }
+    }
+
+    /// Swaps in a new mesh, keeping the transform, materials and skin.
+    pub fn set_mesh(&mut self, mesh: Rc<Mesh>) {
+        self.fit_bounds(&mesh.verts);
+        self.mesh = mesh;
+        if let Some(skin) = self.skin.take() {
+            self.set_skin(skin);
+        }
+    }
+
+    /// Attaches a skin, see `Skin::bind`.
+    pub fn set_skin(&mut self, mut skin: Skin) {
+        skin.bind(&self.mesh);
+        self.skin = Some(skin);
+        self.pose_skin(0.);
+    }
+
+    /// Poses the skin `time` seconds into its clip and fits the bounding volumes around
+    /// the result. Does nothing without a skin. Run it once a frame, everything else
+    /// reads the posed mesh it leaves behind.
+    pub fn pose_skin(&mut self, time: f64) {
+        if let Some(skin) = &mut self.skin {
+            skin.pose(time);
+            let posed = skin.pose_mesh(&self.mesh);
+            self.fit_bounds(&posed.verts);
+            self.posed = Some(posed);
         }
     }
-    pub fn Render(&self, frame: &mut [u8], cam_trans: Transform) {
-        let mut projected = Vec::new();
-        for vert in &self.verts {
-            let mut vert = *vert;
+
+    /// Fits the bounding volumes around object space vertices.
+    fn fit_bounds(&mut self, verts: &Vertices) {
+        self.bounding_sphere = BoundingSphere::new(verts);
+        self.aabb = Aabb::new(verts);
+        self.obb = Obb::new(verts);
+    }
+
+    /// Vertices in object space, after skinning.
+    pub fn object_verts(&self) -> &Vertices {
+        match &self.posed {
+            Some(posed) => &posed.verts,
+            None => &self.mesh.verts,
+        }
+    }
+
+    /// Vertex normals in object space, after skinning.
+    pub fn object_normals(&self) -> &[Vec3] {
+        match &self.posed {
+            Some(posed) => &posed.normals,
+            None => &self.mesh.normals,
+        }
+    }
+
+    /// A direction at vertex `i` in object space, like its normal, after skinning.
+    pub fn object_dir(&self, i: usize, dir: Vec3) -> Vec3 {
+        match &self.skin {
+            Some(skin) => skin.direction(&self.mesh, i, dir),
+            None => dir,
+        }
+    }
+
+    /// Gives the triangles in `tris` their own material, overriding the instance material.
+    pub fn set_material_range(&mut self, tris: Range<usize>, material: Material) {
+        self.sub_materials.push(MaterialRange { tris, material });
+    }
+
+    fn material_for(&self, tri_index: usize) -> &Material {
+        // Later ranges win, so overlapping ranges behave like layers.
+        for range in self.sub_materials.iter().rev() {
+            if range.tris.contains(&tri_index) {
+                return &range.material;
+            }
+        }
+        &self.material
+    }
+
+    pub fn Render(
+        &self,
+        frame: &mut [u8],
+        depth_buffer: &mut [f64],
+        cam_trans: Transform,
+        lighting: &Lighting,
+        options: &RenderOptions,
+        stats: &mut RenderStats,
+    ) {
+        let transform_start = Instant::now();
+        let transformed = self.camera_space_verts(cam_trans);
+        let projected: Vec<Vec2> = transformed.iter().map(|v| project_vertex(*v)).collect();
+        let normals: Vec<Vec3> = self
+            .object_normals()
+            .iter()
+            .map(|n| self.trans.transform_normal(*n, cam_trans))
+            .collect();
+        let tangents: Option<Vec<Vec4>> = self.mesh.tangents.as_ref().map(|tangents| {
+            tangents
+                .iter()
+                .enumerate()
+                .map(|(i, t)| {
+                    self.trans
+                        .transform_normal(self.object_dir(i, t.truncate()), cam_trans)
+                        .extend(t.w)
+                })
+                .collect()
+        });
+        stats.transform_time += transform_start.elapsed();
+
+        let raster_start = Instant::now();
+        // Point clouds ignore the faces of the mesh, if it has any.
+        let triangles: &[Indices] = if self.material.mode == RenderMode::Points {
+            stats.pixels_written +=
+                self.render_points(&transformed, &projected, frame, depth_buffer);
+            &[]
+        } else {
+            &self.mesh.triangles
+        };
+        // Triangles with a custom shader are collected per material and drawn after.
+        let mut custom: Vec<(&Material, Vec<Indices>)> = Vec::new();
+        for (i, tri) in triangles.iter().enumerate() {
+            let material = self.material_for(i);
+            if material.shader.is_some() && options.debug_view == DebugView::Off {
+                match custom.iter_mut().find(|(m, _)| std::ptr::eq(*m, material)) {
+                    Some((_, tris)) => tris.push(*tri),
+                    None => custom.push((material, vec![*tri])),
+                }
+                continue;
+            }
+            let corners = [tri.0, tri.1, tri.2];
+            // Wireframe edges get cut off at the near plane instead.
+            let is_wireframe =
+                material.mode == RenderMode::Wireframe && options.debug_view == DebugView::Off;
+            if !is_wireframe && crosses_near_plane(corners.map(|v| transformed[v])) {
+                stats.triangles_clipped += 1;
+                continue;
+            }
+            let points = corners.map(|v| with_inv_z(projected[v], transformed[v]));
+            if options.debug_view != DebugView::Off {
+                if is_back_facing(corners.map(|v| transformed[v])) {
+                    stats.triangles_culled += 1;
+                    continue;
+                }
+                stats.triangles_rasterized += 1;
+                stats.pixels_written += self.render_debug_triangle(
+                    i,
+                    corners,
+                    points,
+                    &normals,
+                    options.debug_view,
+                    frame,
+                    depth_buffer,
+                );
+                continue;
+            }
+            let culls_back_faces = matches!(material.mode, RenderMode::Shaded | RenderMode::Phong);
+            if culls_back_faces && is_back_facing(corners.map(|v| transformed[v])) {
+                stats.triangles_culled += 1;
+                continue;
+            }
+            stats.triangles_rasterized += 1;
+
+            stats.pixels_written += match material.mode {
+                RenderMode::Wireframe => {
+                    render_wireframe_triangle(
+                        corners.map(|v| transformed[v]),
+                        options.depth_cue.as_ref(),
+                        frame,
+                        material.base_color,
+                    );
+                    0
+                }
+                RenderMode::Filled => self.render_filled_triangle(
+                    corners,
+                    points,
+                    [1.; 3],
+                    material,
+                    frame,
+                    depth_buffer,
+                ),
+                RenderMode::Shaded => {
+                    let [v0, v1, v2] = corners.map(|v| transformed[v]);
+                    let normal = (v1 - v0).cross(v2 - v0);
+                    let center = (v0 + v1 + v2) / 3.;
+                    let intensity = lighting.compute_lighting(center, normal, material.specular);
+                    self.render_filled_triangle(
+                        corners,
+                        points,
+                        [intensity; 3],
+                        material,
+                        frame,
+                        depth_buffer,
+                    )
+                }
+                RenderMode::Phong => self.render_phong_triangle(
+                    corners,
+                    points,
+                    (&transformed, &normals, tangents.as_deref()),
+                    material,
+                    lighting,
+                    frame,
+                    depth_buffer,
+                ),
+                // Whether a mesh is drawn as points is up to the instance material.
+                RenderMode::Points => 0,
+            };
+        }
+
+        stats.raster_time += raster_start.elapsed();
+
+        // Custom shaders time their vertex and raster stages themselves.
+        for (material, tris) in custom {
+            let uniforms = Uniforms {
+                model: self.trans,
+                cam_trans,
+                material,
+                lighting,
+                posed: self.posed.as_ref(),
+            };
+            if let Some(CustomShader(shader)) = &material.shader {
+                shader.render(&self.mesh, &tris, &uniforms, frame, depth_buffer, stats);
+            }
+        }
+
+        // The overdraw view counts writes in the frame, lines would throw that off.
+        if options.show_normals && options.debug_view != DebugView::Overdraw {
+            for (vert, normal) in transformed.iter().zip(&normals) {
+                let tip = *vert + normal * NORMAL_LINE_LENGTH;
+                if vert.z < D || tip.z < D {
+                    continue;
+                }
+                draw_line(project_vertex(*vert), project_vertex(tip), frame, BLAK);
+            }
+        }
+    }
+
+    pub fn camera_space_verts(&self, cam_trans: Transform) -> Vec<Vec3> {
+        let mut transformed = Vec::with_capacity(self.mesh.verts.len());
+        for &vert in self.object_verts() {
+            let mut vert = vert;
             self.trans.apply_transform(&mut vert, cam_trans);
-            projected.push(project_vertex(vert));
+            transformed.push(vert);
         }
+        transformed
+    }
 
-        let colors = vec![WHITE, GREEN, DARK_GREEN, BCK, BLAK, BLCK];
-        let mut i = 0;
+    #[allow(clippy::too_many_arguments)]
+    fn render_debug_triangle(
+        &self,
+        tri_index: usize,
+        corners: [usize; 3],
+        points: [Vec3; 3],
+        normals: &[Vec3],
+        view: DebugView,
+        frame: &mut [u8],
+        depth_buffer: &mut [f64],
+    ) -> usize {
+        match view {
+            DebugView::Off => 0,
+            DebugView::Overdraw => {
+                let mut written = 0;
+                rasterize(points, [[]; 3], CANVAS_SIZE, |i, z, []| {
+                    if z <= depth_buffer[i] {
+                        return;
+                    }
+                    depth_buffer[i] = z;
+                    let writes = frame[i * 4].saturating_add(1);
+                    put_pixel(frame, i, [writes, 0, 0]);
+                    written += 1;
+                });
+                written
+            }
+            // `DebugView::finish` colors the pixels from the depth buffer.
+            DebugView::Depth => draw_triangle(points, [[]; 3], frame, depth_buffer, |[]| BLACK),
+            DebugView::Normals => {
+                let attrs = corners.map(|v| normals[v]);
+                draw_triangle(points, attrs, frame, depth_buffer, |normal| {
+                    let normal = normal.normalize() * 0.5 + Vec3::new(0.5, 0.5, 0.5);
+                    [normal.x, normal.y, normal.z].map(|c| (c * 255.) as u8)
+                })
+            }
+            DebugView::Uvs => {
+                let attrs = corners.map(|v| {
+                    let uv = self.mesh.uvs.as_ref().map_or(Vec2::zero(), |uvs| uvs[v]);
+                    [uv.x, uv.y]
+                });
+                draw_triangle(points, attrs, frame, depth_buffer, |[u, v]| {
+                    [
+                        ((u - u.floor()) * 255.) as u8,
+                        ((v - v.floor()) * 255.) as u8,
+                        0,
+                    ]
+                })
+            }
+            DebugView::TriangleIds => {
+                let color = id_color(tri_index);
+                draw_triangle(points, [[]; 3], frame, depth_buffer, |[]| color)
+            }
+        }
+    }
 
-        for tri in &self.triangles {
-            render_wireframe_triangle(*tri, &projected, frame, colors[i]);
-            //render_filled_triangle(*tri, &projected, frame, colors[i]);
-            if i >= colors.len() - 1 {
-                i = 0;
-            } else {
-                i += 1;
+    /// Draws every vertex as an unlit dot, colored the way `RenderMode::Filled` would
+    /// color it there.
+    fn render_points(
+        &self,
+        transformed: &[Vec3],
+        projected: &[Vec2],
+        frame: &mut [u8],
+        depth_buffer: &mut [f64],
+    ) -> usize {
+        let material = &self.material;
+        let mut written = 0;
+        for (i, (&vert, &point)) in transformed.iter().zip(projected).enumerate() {
+            if vert.z < D {
+                continue;
             }
+            let size = match material.point_distance {
+                Some(distance) => material.point_size * distance / vert.z,
+                None => material.point_size,
+            };
+            let color = match (&material.texture, &self.mesh.uvs, &self.mesh.colors) {
+                (Some(texture), Some(uvs), _) => texture.sample(uvs[i].x, uvs[i].y),
+                (_, _, Some(colors)) => colors[i],
+                _ => material.base_color,
+            };
+            written += draw_point(point, 1. / vert.z, size, color, frame, depth_buffer);
         }
+        written
+    }
+
+    fn render_filled_triangle(
+        &self,
+        corners: [usize; 3],
+        points: [Vec3; 3],
+        intensities: [f64; 3],
+        material: &Material,
+        frame: &mut [u8],
+        depth_buffer: &mut [f64],
+    ) -> usize {
+        if let (Some(texture), Some(uvs)) = (&material.texture, &self.mesh.uvs) {
+            let attrs = [0, 1, 2].map(|c| {
+                let uv = uvs[corners[c]];
+                [uv.x, uv.y, intensities[c]]
+            });
+            draw_triangle(points, attrs, frame, depth_buffer, |[u, v, intensity]| {
+                shade_color(texture.sample(u, v), intensity)
+            })
+        } else if let Some(colors) = &self.mesh.colors {
+            let attrs = [0, 1, 2].map(|c| {
+                let [r, g, b] = colors[corners[c]].map(|channel| channel as f64);
+                [r, g, b, intensities[c]]
+            });
+            draw_triangle(points, attrs, frame, depth_buffer, |[r, g, b, intensity]| {
+                shade_color([r, g, b].map(|channel| channel as u8), intensity)
+            })
+        } else {
+            let attrs = intensities.map(|intensity| [intensity]);
+            draw_triangle(points, attrs, frame, depth_buffer, |[intensity]| {
+                shade_color(material.base_color, intensity)
+            })
+        }
+    }
+
+    /// Per-pixel lighting: interpolates the camera space normal and position across the
+    /// triangle and lights every pixel, so highlights smaller than a triangle survive.
+    #[allow(clippy::too_many_arguments)]
+    fn render_phong_triangle(
+        &self,
+        corners: [usize; 3],
+        points: [Vec3; 3],
+        (transformed, normals, tangents): (&[Vec3], &[Vec3], Option<&[Vec4]>),
+        material: &Material,
+        lighting: &Lighting,
+        frame: &mut [u8],
+        depth_buffer: &mut [f64],
+    ) -> usize {
+        let uvs = self.mesh.uvs.as_ref();
+        let texture = material.texture.as_ref().filter(|_| uvs.is_some());
+        let normal_map = match (&material.normal_map, tangents) {
+            (Some(normal_map), Some(_)) if uvs.is_some() => Some(normal_map),
+            _ => None,
+        };
+        let colors = self.mesh.colors.as_ref();
+        let attrs = corners.map(|v| {
+            let (n, p) = (normals[v], transformed[v]);
+            let uv = uvs.map_or(Vec2::zero(), |uvs| uvs[v]);
+            let t = tangents.map_or(Vec4::zero(), |tangents| tangents[v]);
+            let [r, g, b] = colors.map_or([0.; 3], |colors| colors[v].map(|c| c as f64));
+            [
+                n.x, n.y, n.z, p.x, p.y, p.z, uv.x, uv.y, t.x, t.y, t.z, t.w, r, g, b,
+            ]
+        });
+
+        draw_triangle(
+            points,
+            attrs,
+            frame,
+            depth_buffer,
+            |[nx, ny, nz, px, py, pz, u, v, tx, ty, tz, tw, r, g, b]| {
+                let mut normal = Vec3::new(nx, ny, nz);
+                if let Some(normal_map) = normal_map {
+                    let normal_ts = normal_map.sample_normal(u, v);
+                    let tangent = Vec3::new(tx, ty, tz);
+                    let bitangent = normal.cross(tangent) * tw.signum();
+                    normal = tangent * normal_ts.x + bitangent * normal_ts.y + normal * normal_ts.z;
+                }
+                let point = Vec3::new(px, py, pz);
+                let intensity = lighting.compute_lighting(point, normal, material.specular);
+                let color = match (texture, colors) {
+                    (Some(texture), _) => texture.sample(u, v),
+                    (None, Some(_)) => [r, g, b].map(|c| c as u8),
+                    (None, None) => material.base_color,
+                };
+                let color = shade_color(color, intensity);
+                if material.reflectivity > 0. {
+                    let reflection = lighting.reflection(point, normal);
+                    blend_colors(color, reflection, material.reflectivity)
+                } else {
+                    color
+                }
+            },
+        )
     }
 }
 
+/// Back-facing triangles are hidden behind the front of a closed mesh.
+pub fn is_back_facing([v0, v1, v2]: [Vec3; 3]) -> bool {
+    (v1 - v0).cross(v2 - v0).dot(v0) >= 0.
+}
+
+/// Filled triangles aren't clipped against the near plane, so ones reaching past it
+/// get dropped: their corners would project to huge or mirrored screen coordinates.
+pub fn crosses_near_plane(verts: [Vec3; 3]) -> bool {
+    verts.iter().any(|v| v.z < D)
+}
+
+/// Packs a projected point together with 1/z of its camera space vertex for depth testing.
+pub fn with_inv_z(projected: Vec2, transformed: Vec3) -> Vec3 {
+    Vec3::new(projected.x, projected.y, 1. / transformed.z)
+}
+
+/// Switches that change how every instance gets drawn.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    /// Fades wireframe lines with depth, they can't be fogged afterwards like surfaces.
+    pub depth_cue: Option<Fog>,
+    pub show_normals: bool,
+    pub debug_view: DebugView,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Transform {
     pub translation: Vec3,
@@ -59,84 +509,142 @@ impl Transform {
             rot: 0.,
         }
     }
-    fn apply_transform(&self, vert: &mut Vec3, cam_trans: Transform) {
+    pub fn apply_transform(&self, vert: &mut Vec3, cam_trans: Transform) {
+        *vert = cam_trans.to_camera_space(self.to_parent_space(*vert));
+    }
+    /// Rotates, scales and then translates a point, e.g. from object into world space.
+    pub fn to_parent_space(self, point: Vec3) -> Vec3 {
+        self.rotate(point) * self.scale + self.translation
+    }
+    /// Transform that applies `child` first and then `self`. Rotations are all about y
+    /// and scaling is uniform, so the result is again a `Transform`.
+    pub fn combine(self, child: Transform) -> Transform {
+        Transform {
+            translation: self.to_parent_space(child.translation),
+            scale: self.scale * child.scale,
+            rot: self.rot + child.rot,
+        }
+    }
+    pub fn matrix(self) -> Mat4 {
+        Mat4::from_translation(self.translation)
+            * Mat4::from_angle_y(Deg(self.rot))
+            * Mat4::from_scale(self.scale)
+    }
+    /// Transform that undoes `self`.
+    pub fn inverse(self) -> Transform {
+        let inverse = Transform {
+            translation: Vec3::zero(),
+            scale: 1. / self.scale,
+            rot: -self.rot,
+        };
+        Transform {
+            translation: -inverse.to_parent_space(self.translation),
+            ..inverse
+        }
+    }
+    /// Rotates a direction from the space `self` maps from into the one it maps to.
+    pub fn rotate(self, dir: Vec3) -> Vec3 {
         let rot_self: Basis3<f64> = Rotation3::from_angle_y(Deg(self.rot));
-        let rot_cam: Basis3<f64> = Rotation3::from_angle_y(Deg(cam_trans.rot));
-
-        *vert = rot_self.rotate_vector(*vert) * self.scale;
-        *vert += self.translation + cam_trans.translation + DEFAULT_TRANSL;
-        *vert = rot_cam.rotate_vector(*vert);
+        rot_self.rotate_vector(dir)
+    }
+    /// Rotates a normal the same way `apply_transform` rotates vertices. Scaling is
+    /// uniform so it doesn't change the direction.
+    pub fn transform_normal(&self, normal: Vec3, cam_trans: Transform) -> Vec3 {
+        cam_trans.rotate_to_camera(self.rotate(normal))
+    }
+    /// Treats `self` as the camera transform and moves a world space point into camera space.
+    pub fn to_camera_space(self, point: Vec3) -> Vec3 {
+        self.rotate_to_camera(point + self.translation + DEFAULT_TRANSL)
+    }
+    /// Same as `to_camera_space` but for directions, which are only rotated.
+    pub fn rotate_to_camera(self, dir: Vec3) -> Vec3 {
+        let rot_cam: Basis3<f64> = Rotation3::from_angle_y(Deg(self.rot));
+        rot_cam.rotate_vector(dir)
+    }
+    /// Undoes `rotate_to_camera`, turning a camera space direction back into world space.
+    pub fn rotate_from_camera(self, dir: Vec3) -> Vec3 {
+        let rot_cam: Basis3<f64> = Rotation3::from_angle_y(Deg(-self.rot));
+        rot_cam.rotate_vector(dir)
     }
 }
 
+/// Outlines a triangle. With `depth_cue` every corner fades by its depth, and the
+/// lines blend between them.
 fn render_wireframe_triangle(
-    tri: Indices,
-    projected: &Vec<Vec2>,
+    verts: [Vec3; 3],
+    depth_cue: Option<&Fog>,
     frame: &mut [u8],
     color: [u8; 3],
 ) {
-    draw_wireframe_triangle(
-        projected[tri.0],
-        projected[tri.1],
-        projected[tri.2],
-        frame,
-        color,
-    );
-}
-fn render_filled_triangle(tri: Indices, projected: &Vec<Vec2>, frame: &mut [u8], color: [u8; 3]) {
-    draw_triangle(
-        projected[tri.0],
-        projected[tri.1],
-        projected[tri.2],
-        frame,
-        color,
-    );
-}
-
-#[derive(Debug, Clone, Copy)]
+    let cue = |v: Vec3| depth_cue.map_or(color, |fog| fog.apply(color, v.z));
+    for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+        if let Some((v0, v1)) = clip_segment_to_near(verts[a], verts[b]) {
+            draw_shaded_line(
+                project_vertex(v0),
+                project_vertex(v1),
+                frame,
+                [cue(v0), cue(v1)],
+            );
+        }
+    }
+}
+
+/// Built-in meshes. All of them are centered on the origin and fit in the same
+/// -1..1 box as the cube, unless a size parameter says otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
 pub enum Model {
     Cube,
+    UvSphere {
+        segments: usize,
+        rings: usize,
+    },
+    Icosphere {
+        subdivisions: usize,
+    },
+    Cylinder {
+        segments: usize,
+    },
+    Cone {
+        segments: usize,
+    },
+    Torus {
+        segments: usize,
+        sides: usize,
+        tube_radius: f64,
+    },
+    Plane {
+        subdivisions: usize,
+    },
+    Capsule {
+        segments: usize,
+        rings: usize,
+        height: f64,
+    },
 }
 impl Model {
-    pub fn get_verts(&self) -> Vertices {
-        match self {
-            Model::Cube => {
-                vec![
-                    Vec3::new(1., 1., 1.),
-                    Vec3::new(-1., 1., 1.),
-                    Vec3::new(-1., -1., 1.),
-                    Vec3::new(1., -1., 1.),
-                    Vec3::new(1., 1., -1.),
-                    Vec3::new(-1., 1., -1.),
-                    Vec3::new(-1., -1., -1.),
-                    Vec3::new(1., -1., -1.),
-                ]
-            }
-        }
-    }
-    pub fn get_indices(&self) -> Vec<Indices> {
-        match self {
-            Model::Cube => {
-                vec![
-                    (0, 1, 2),
-                    (0, 2, 3),
-                    (4, 0, 3),
-                    (4, 3, 7),
-                    (5, 4, 7),
-                    (5, 7, 6),
-                    (1, 5, 6),
-                    (1, 6, 2),
-                    (4, 5, 1),
-                    (4, 1, 0),
-                    (2, 6, 7),
-                    (2, 7, 3),
-                ]
-            }
+    pub fn get_mesh(&self) -> Mesh {
+        match *self {
+            Model::Cube => primitives::cube(),
+            Model::UvSphere { segments, rings } => primitives::uv_sphere(segments, rings),
+            Model::Icosphere { subdivisions } => primitives::icosphere(subdivisions),
+            Model::Cylinder { segments } => primitives::cylinder(segments),
+            Model::Cone { segments } => primitives::cone(segments),
+            Model::Torus {
+                segments,
+                sides,
+                tube_radius,
+            } => primitives::torus(segments, sides, tube_radius),
+            Model::Plane { subdivisions } => primitives::plane(subdivisions),
+            Model::Capsule {
+                segments,
+                rings,
+                height,
+            } => primitives::capsule(segments, rings, height),
         }
     }
 }
 
-fn project_vertex(vert: Vec3) -> Vec2 {
+pub fn project_vertex(vert: Vec3) -> Vec2 {
     viewport_to_canvas(vert.x * D / vert.z, vert.y * D / vert.z)
 }
 
@@ -148,6 +656,15 @@ fn viewport_to_canvas(x: f64, y: f64) -> Vec2 {
     )
 }
 
+/// Inverse of `viewport_to_canvas`.
+pub fn canvas_to_viewport(x: f64, y: f64) -> Vec2 {
+    let (canvas_size, viewport_size) = (CANVAS_SIZE as f64, VIEWPORT_SIZE as f64);
+    Vec2::new(
+        x * viewport_size / canvas_size,
+        y * viewport_size / canvas_size,
+    )
+}
+
 pub fn pr<T: std::fmt::Display>(s: T) {
     println!("{}", s);
 }
@@ -0,0 +1,172 @@
+use crate::{
+    consts::*,
+    environment::Background,
+    instance::{Instance, Transform},
+    shadow::ShadowMap,
+};
+use cgmath::*;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    Ambient {
+        intensity: f64,
+    },
+    Point {
+        intensity: f64,
+        position: Vec3,
+    },
+    /// `direction` is the direction the light travels in.
+    Directional {
+        intensity: f64,
+        direction: Vec3,
+        shadows: bool,
+    },
+    /// Only lights points within `angle` degrees of `direction`.
+    Spot {
+        intensity: f64,
+        position: Vec3,
+        direction: Vec3,
+        angle: f64,
+        shadows: bool,
+    },
+}
+impl Light {
+    /// Moves the light from world space into the space of the camera.
+    pub fn to_camera_space(self, cam_trans: Transform) -> Light {
+        match self {
+            Light::Ambient { .. } => self,
+            Light::Point {
+                intensity,
+                position,
+            } => Light::Point {
+                intensity,
+                position: cam_trans.to_camera_space(position),
+            },
+            Light::Directional {
+                intensity,
+                direction,
+                shadows,
+            } => Light::Directional {
+                intensity,
+                direction: cam_trans.rotate_to_camera(direction),
+                shadows,
+            },
+            Light::Spot {
+                intensity,
+                position,
+                direction,
+                angle,
+                shadows,
+            } => Light::Spot {
+                intensity,
+                position: cam_trans.to_camera_space(position),
+                direction: cam_trans.rotate_to_camera(direction),
+                angle,
+                shadows,
+            },
+        }
+    }
+}
+
+/// The lights of a scene moved into camera space, together with the shadow maps of
+/// the ones that cast shadows. Rebuilt every frame.
+pub struct Lighting {
+    lights: Vec<Light>,
+    shadow_maps: Vec<Option<ShadowMap>>,
+    environment: Background,
+    cam_trans: Transform,
+}
+impl Lighting {
+    pub fn new(
+        lights: &[Light],
+        environment: &Background,
+        instances: &[Instance],
+        cam_trans: Transform,
+    ) -> Self {
+        let lights: Vec<Light> = lights
+            .iter()
+            .map(|light| light.to_camera_space(cam_trans))
+            .collect();
+        let shadow_maps = lights
+            .iter()
+            .map(|light| ShadowMap::render(light, instances, cam_trans))
+            .collect();
+        Self {
+            lights,
+            shadow_maps,
+            environment: environment.clone(),
+            cam_trans,
+        }
+    }
+
+    /// Environment color mirrored at `point` with surface normal `normal`, both in
+    /// camera space.
+    pub fn reflection(&self, point: Vec3, normal: Vec3) -> [u8; 3] {
+        let normal = normal.normalize();
+        let reflected = point - normal * 2. * normal.dot(point);
+        self.environment
+            .sample(self.cam_trans.rotate_from_camera(reflected))
+    }
+
+    /// Light intensity at `point` with surface normal `normal`, both in camera space.
+    /// The camera sits at the origin so the view vector is simply `-point`.
+    pub fn compute_lighting(&self, point: Vec3, normal: Vec3, specular: f64) -> f64 {
+        let view = -point;
+        let mut intensity = 0.;
+
+        for (light, shadow_map) in self.lights.iter().zip(&self.shadow_maps) {
+            let (light_intensity, l) = match *light {
+                Light::Ambient { intensity: i } => {
+                    intensity += i;
+                    continue;
+                }
+                Light::Point {
+                    intensity,
+                    position,
+                } => (intensity, position - point),
+                Light::Directional {
+                    intensity,
+                    direction,
+                    ..
+                } => (intensity, -direction),
+                Light::Spot {
+                    intensity,
+                    position,
+                    direction,
+                    angle,
+                    ..
+                } => {
+                    let l = position - point;
+                    if (-l).angle(direction) > Deg(angle).into() {
+                        continue;
+                    }
+                    (intensity, l)
+                }
+            };
+
+            let visibility = match shadow_map {
+                Some(shadow_map) => shadow_map.visibility(point),
+                None => 1.,
+            };
+            if visibility == 0. {
+                continue;
+            }
+            let light_intensity = light_intensity * visibility;
+
+            let n_dot_l = normal.dot(l);
+            if n_dot_l > 0. {
+                intensity += light_intensity * n_dot_l / (normal.magnitude() * l.magnitude());
+            }
+
+            if specular != -1. {
+                let r = normal * 2. * n_dot_l / normal.magnitude2() - l;
+                let r_dot_v = r.dot(view);
+                if r_dot_v > 0. {
+                    intensity += light_intensity
+                        * (r_dot_v / (r.magnitude() * view.magnitude())).powf(specular);
+                }
+            }
+        }
+        intensity
+    }
+}
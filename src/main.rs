@@ -1,15 +1,52 @@
 #![allow(non_snake_case)]
 #![allow(dead_code)]
 
+mod animation;
+mod bvh;
 mod clipping;
 mod consts;
+mod debug_view;
+mod environment;
+mod fog;
+mod gizmo;
+mod gltf_import;
+mod hud;
 mod instance;
-use clipping::{clip_scene, Plane};
+mod light;
+mod material;
+mod mesh;
+mod outline;
+mod picking;
+mod ply;
+mod primitives;
+mod reload;
+mod scene;
+mod scene_graph;
+mod scene_save;
+mod shader;
+mod shadow;
+mod skeleton;
+mod stats;
+mod stl;
+mod text;
+use bvh::Bvh;
+use clipping::{clip_scene, CullingVolume, Plane};
 use consts::*;
-use instance::{Transform, *};
-
-use log::error;
+use debug_view::DebugView;
+use environment::Background;
+use gizmo::Gizmo;
+use hud::Hud;
+use instance::{Instance, RenderOptions};
+use light::Lighting;
+use picking::Ray;
+use reload::SceneWatcher;
+use scene::Scene;
+use shader::Varyings;
+use stats::{RenderStats, StatsCsv};
+
+use log::{error, info};
 use pixels::{Error, PixelsBuilder, SurfaceTexture};
+use std::time::Instant;
 use winit::dpi::LogicalSize;
 use winit::event::{Event, VirtualKeyCode};
 use winit::event_loop::{ControlFlow, EventLoop};
@@ -36,46 +73,176 @@ fn main() -> Result<(), Error> {
 
     //pixels.set_clear_color(Color::BLACK);
 
-    let mut instances = vec![
-        Instance::new(Model::Cube, Vec3::new(0., 0., 0.), 1.),
-        //Instance::new(Model::Cube, Vec3::new(2.5, 0., 0.), 1.),
-    ];
+    // Usage: renderer [scene.ron] [--stats stats.csv]
+    let (mut scene_path, mut stats_path) = (None, None);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--stats" {
+            stats_path = args.next();
+        } else {
+            scene_path = Some(arg);
+        }
+    }
+    let mut stats_csv = stats_path.and_then(|path| {
+        StatsCsv::create(&path)
+            .map_err(|e| error!("can't write stats to {}: {}", path, e))
+            .ok()
+    });
+    let mut scene = match &scene_path {
+        Some(path) => Scene::load(path).unwrap_or_else(|e| {
+            error!("failed to load scene {}: {}", path, e);
+            std::process::exit(1)
+        }),
+        None => Scene::default(),
+    };
+    let mut watcher = scene_path
+        .clone()
+        .map(|path| SceneWatcher::new(path, &scene));
+    let mut cam_trans = scene.camera;
     let clipping_planes = vec![
         Plane::new(Vec3::new(0., 0., 1.), -D),                  //near
         Plane::new(Vec3::new(1. / 1.414, 0., 1. / 1.414), 0.),  //left
         Plane::new(Vec3::new(-1. / 1.414, 0., 1. / 1.414), 0.), //right
         Plane::new(Vec3::new(0., 1. / 1.414, 1. / 1.414), 0.),  //bottom
-        Plane::new(Vec3::new(0., 0., -1. / 1.414 / 1.414), 0.), //top
+        Plane::new(Vec3::new(0., -1. / 1.414, 1. / 1.414), 0.), //top
     ];
 
-    let mut cam_trans = Transform::new(Vec3::new(0., 0., 0.), 1.);
-    let mut cam_is_current_trans = false;
+    let mut show_normals = false;
+    let mut depth_cueing = false;
+    let mut debug_view = DebugView::Off;
+    let mut wireframe_overlay = false;
+    let mut culling_volume = CullingVolume::default();
+    // Instances the gizmo edits, which sits on the last one.
+    let mut selection: Vec<usize> = Vec::new();
+    let mut gizmo = Gizmo::new();
+    let mut bvh = Bvh::new(&scene.instances);
+    let mut hovered_handle = None;
 
-    let mut last_frame = std::time::Instant::now();
-    let mut frames_passed = 0;
-    let mut total_frame_time = 0.;
+    let mut depth_buffer = vec![0.; (CANVAS_SIZE * CANVAS_SIZE) as usize];
+
+    let mut hud = Hud::new();
+    let mut last_frame = Instant::now();
 
     event_loop.run(move |event, _, control_flow| {
         if let Event::RedrawRequested(_) = event {
-            total_frame_time += last_frame.elapsed().as_secs_f64();
-            last_frame = std::time::Instant::now();
-            frames_passed += 1;
+            let frame_time = last_frame.elapsed().as_secs_f64();
+            hud.record_frame(frame_time);
+            last_frame = Instant::now();
+
+            if let Some(watcher) = &mut watcher {
+                watcher.poll(&mut scene);
+            }
+            // A reload may leave fewer instances behind.
+            selection.retain(|&i| i < scene.instances.len());
 
             let screen_frame = pixels.get_frame_mut();
-            clear_screen(screen_frame);
+            if debug_view == DebugView::Off {
+                scene.background.draw(screen_frame, cam_trans);
+            } else {
+                Background::default().draw(screen_frame, cam_trans);
+            }
+            depth_buffer.fill(0.);
 
-            for instance in &mut instances {
-                instance.bounding_sphere.update(
-                    instance.trans.translation + cam_trans.translation,
-                    cam_trans.rot,
-                );
+            let duration = scene.animation_duration();
+            scene.clock.advance(frame_time, duration);
+            animation::animate(&scene.animations, scene.clock.time, &mut scene.graph);
+            for instance in &mut scene.instances {
+                instance.pose_skin(scene.clock.time);
             }
+            scene.graph.update(&mut scene.instances);
+            bvh.update(&scene.instances);
+
+            let mut stats = RenderStats::default();
+            let lighting_start = Instant::now();
+            let lighting = Lighting::new(
+                &scene.lights,
+                &scene.background,
+                &scene.instances,
+                cam_trans,
+            );
+            stats.lighting_time = lighting_start.elapsed();
+
+            let options = RenderOptions {
+                depth_cue: if depth_cueing {
+                    Some(scene.depth_cue.unwrap_or_default())
+                } else {
+                    scene.fog
+                },
+                show_normals,
+                debug_view,
+            };
+
+            let clipping_start = Instant::now();
+            let clipped_instances = clip_scene(
+                &bvh,
+                &scene.instances,
+                &clipping_planes,
+                cam_trans,
+                culling_volume,
+            );
+            stats.clipping_time = clipping_start.elapsed();
+
+            let triangles = |instance: &Instance| instance.mesh.triangles.len();
+            stats.instances_submitted = scene.instances.len();
+            stats.instances_culled = scene.instances.len() - clipped_instances.len();
+            stats.triangles_submitted = scene.instances.iter().map(triangles).sum();
+            stats.triangles_clipped = stats.triangles_submitted
+                - clipped_instances
+                    .iter()
+                    .map(|i| triangles(i))
+                    .sum::<usize>();
 
-            let clipped_instances = clip_scene(&instances, &clipping_planes);
             for instance in &clipped_instances {
-                instance.Render(screen_frame, cam_trans);
+                instance.Render(
+                    screen_frame,
+                    &mut depth_buffer,
+                    cam_trans,
+                    &lighting,
+                    &options,
+                    &mut stats,
+                );
             }
-
+            stats.pixels_covered = depth_buffer.iter().filter(|&&inv_z| inv_z > 0.).count();
+            match scene.fog {
+                Some(fog) if debug_view == DebugView::Off => {
+                    fog.apply_to_frame(screen_frame, &depth_buffer)
+                }
+                _ => debug_view.finish(screen_frame, &depth_buffer),
+            }
+            // Drawn after the fog so it doesn't fade what's selected. The outline shows
+            // through whatever is in front, the wireframe overlay doesn't.
+            if !selection.is_empty() {
+                let selected: Vec<&Instance> =
+                    selection.iter().map(|&i| &scene.instances[i]).collect();
+                if wireframe_overlay {
+                    for instance in &selected {
+                        outline::draw_wireframe_overlay(
+                            screen_frame,
+                            &depth_buffer,
+                            instance,
+                            cam_trans,
+                            OVERLAY_COLOR,
+                        );
+                    }
+                }
+                let mask = outline::coverage_mask(&selected, cam_trans);
+                outline::draw_outline(screen_frame, &mask, OUTLINE_WIDTH, OUTLINE_COLOR);
+            }
+            if let Some(&active) = selection.last() {
+                let pivot = scene.instances[active].trans.translation;
+                gizmo.draw(screen_frame, pivot, cam_trans, hovered_handle);
+            }
+            hud.draw(
+                screen_frame,
+                &stats,
+                cam_trans,
+                selection.len(),
+                gizmo.mode,
+                culling_volume,
+                debug_view,
+            );
+
+            let present_start = Instant::now();
             if pixels
                 .render()
                 .map_err(|e| error!("pixels.render() failed: {}", e))
@@ -84,6 +251,14 @@ fn main() -> Result<(), Error> {
                 *control_flow = ControlFlow::Exit;
                 return;
             }
+            stats.present_time = present_start.elapsed();
+
+            if let Some(csv) = &mut stats_csv {
+                if let Err(e) = csv.write(&stats) {
+                    error!("failed to write stats: {}", e);
+                    stats_csv = None;
+                }
+            }
         }
         // Handle input events
         if input.update(&event) {
@@ -97,67 +272,115 @@ fn main() -> Result<(), Error> {
                 pixels.resize_surface(size.width, size.height);
             }
 
-            if input.mouse_pressed(0) {
-                pr(frames_passed as f64 / total_frame_time);
-                cam_is_current_trans = !cam_is_current_trans;
+            let cursor = input
+                .mouse()
+                .and_then(|position| pixels.window_pos_to_pixel(position).ok())
+                .map(picking::pixel_to_canvas);
+            let pivot = selection
+                .last()
+                .map(|&i| scene.instances[i].trans.translation);
+            if !gizmo.is_dragging() {
+                hovered_handle = pivot
+                    .zip(cursor)
+                    .and_then(|(pivot, cursor)| gizmo.handle_at(pivot, cam_trans, cursor));
             }
 
-            let trans_speed = TRANS_SPEED * last_frame.elapsed().as_secs_f64();
-
-            let mut transl_this_frame = Vec3::new(0., 0., 0.);
-            let mut rot_this_frame = 0.;
-            let mut scale_this_frame = 0.;
-
-            if input.key_held(VirtualKeyCode::W) {
-                let translation = Vec3::new(0., 0., trans_speed);
-                if cam_is_current_trans {
-                    cam_trans.translation += -translation;
+            // Clicking a gizmo handle drags it. Clicking an instance selects it and
+            // clicking nothing clears the selection, shift adds to or removes from it
+            // instead.
+            if input.mouse_pressed(0) {
+                if let (Some(handle), Some(pivot), Some(cursor)) = (hovered_handle, pivot, cursor) {
+                    let ray = Ray::through_canvas(cursor).to_world(cam_trans);
+                    gizmo.begin_drag(handle, pivot, (ray, cam_trans), &scene.graph, &selection);
                 } else {
-                    transl_this_frame += translation;
+                    let hit = cursor.and_then(|cursor| {
+                        picking::pick(&scene.instances, &bvh, cam_trans, cursor)
+                    });
+                    match hit {
+                        Some(hit) if input.held_shift() => {
+                            match selection.iter().position(|&i| i == hit.instance) {
+                                Some(at) => {
+                                    selection.remove(at);
+                                }
+                                None => selection.push(hit.instance),
+                            }
+                        }
+                        Some(hit) => selection = vec![hit.instance],
+                        None if input.held_shift() => (),
+                        None => selection.clear(),
+                    }
                 }
             }
-            if input.key_held(VirtualKeyCode::A) {
-                let translation = Vec3::new(-trans_speed, 0., 0.);
-                if cam_is_current_trans {
-                    cam_trans.translation += translation;
-                } else {
-                    transl_this_frame += translation;
+            if gizmo.is_dragging() {
+                if let Some(cursor) = cursor {
+                    gizmo.drag(
+                        Ray::through_canvas(cursor).to_world(cam_trans),
+                        &mut scene.graph,
+                    );
                 }
-            }
-            if input.key_held(VirtualKeyCode::S) {
-                let translation = Vec3::new(0., 0., -trans_speed);
-                if cam_is_current_trans {
-                    cam_trans.translation += -translation;
-                } else {
-                    transl_this_frame += translation;
+                if input.mouse_released(0) {
+                    gizmo.end_drag();
                 }
             }
-            if input.key_held(VirtualKeyCode::D) {
-                let translation = Vec3::new(trans_speed, 0., 0.);
-                if cam_is_current_trans {
-                    cam_trans.translation += translation;
-                } else {
-                    transl_this_frame += translation;
+            if input.key_pressed(VirtualKeyCode::G) {
+                gizmo.mode = gizmo.mode.next();
+            }
+            if input.held_control() && input.key_pressed(VirtualKeyCode::S) {
+                match &scene_path {
+                    Some(path) => match scene.save_transforms(path) {
+                        Ok(()) => info!("saved {}", path),
+                        Err(e) => error!("{}", e),
+                    },
+                    None => error!("there's no scene file to save to"),
                 }
             }
 
-            if input.key_held(VirtualKeyCode::Q) {
-                if !cam_is_current_trans {
-                    scale_this_frame += trans_speed;
-                }
+            if input.key_pressed(VirtualKeyCode::N) {
+                show_normals = !show_normals;
             }
-            if input.key_held(VirtualKeyCode::R) {
-                if cam_is_current_trans {
-                    cam_trans.rot += trans_speed * 30.;
-                } else {
-                    rot_this_frame += trans_speed * 30.;
-                }
+            if input.key_pressed(VirtualKeyCode::C) {
+                depth_cueing = !depth_cueing;
+            }
+            if input.key_pressed(VirtualKeyCode::V) {
+                debug_view = debug_view.next();
+            }
+            if input.key_pressed(VirtualKeyCode::B) {
+                culling_volume = culling_volume.next();
             }
+            if input.key_pressed(VirtualKeyCode::O) {
+                wireframe_overlay = !wireframe_overlay;
+            }
+            if input.key_pressed(VirtualKeyCode::H) {
+                hud.visible = !hud.visible;
+            }
+            if input.key_pressed(VirtualKeyCode::P) {
+                scene.clock.toggle_playing();
+            }
+            if input.key_pressed(VirtualKeyCode::L) {
+                scene.clock.looping = !scene.clock.looping;
+            }
+            if input.key_pressed(VirtualKeyCode::Home) {
+                scene.clock.restart();
+            }
+
+            let trans_speed = TRANS_SPEED * last_frame.elapsed().as_secs_f64();
 
-            for inst in &mut instances {
-                inst.trans.translation += transl_this_frame;
-                inst.trans.scale += scale_this_frame;
-                inst.trans.rot += rot_this_frame;
+            // Keeps Ctrl+S from nudging the camera.
+            let moving = !input.held_control();
+            if moving && input.key_held(VirtualKeyCode::W) {
+                cam_trans.translation.z -= trans_speed;
+            }
+            if moving && input.key_held(VirtualKeyCode::A) {
+                cam_trans.translation.x -= trans_speed;
+            }
+            if moving && input.key_held(VirtualKeyCode::S) {
+                cam_trans.translation.z += trans_speed;
+            }
+            if moving && input.key_held(VirtualKeyCode::D) {
+                cam_trans.translation.x += trans_speed;
+            }
+            if moving && input.key_held(VirtualKeyCode::R) {
+                cam_trans.rot += trans_speed * 30.;
             }
 
             window.request_redraw();
@@ -165,52 +388,147 @@ fn main() -> Result<(), Error> {
     });
 }
 
-fn draw_triangle(mut p0: Vec2, mut p1: Vec2, mut p2: Vec2, frame: &mut [u8], color: [u8; 3]) {
-    if p0.y > p1.y {
-        swap(&mut p0, &mut p1)
-    }
-    if p1.y > p2.y {
-        swap(&mut p1, &mut p2)
-    }
-    if p0.y > p1.y {
-        swap(&mut p0, &mut p1)
-    }
-    let (x0, y0, x1, y1, x2, y2) = (p0.x, p0.y, p1.x, p1.y, p2.x, p2.y);
+/// Fills a triangle whose points carry 1/z in their z component, skipping pixels that
+/// lie behind something already in the depth buffer. The per-vertex `attrs` are
+/// interpolated perspective correctly and handed to `shade`, which picks the pixel color.
+/// Returns how many pixels were written.
+fn draw_triangle<V: Varyings>(
+    points: [Vec3; 3],
+    attrs: [V; 3],
+    frame: &mut [u8],
+    depth_buffer: &mut [f64],
+    mut shade: impl FnMut(V) -> [u8; 3],
+) -> usize {
+    let mut written = 0;
+    rasterize(points, attrs, CANVAS_SIZE, |i, z, attrs| {
+        if z <= depth_buffer[i] {
+            return;
+        }
+        depth_buffer[i] = z;
+        put_pixel(frame, i, shade(attrs));
+        written += 1;
+    });
+    written
+}
 
-    let mut x01 = interpolate(y0, x0, y1, x1);
-    let mut x12 = interpolate(y1, x1, y2, x2);
-    let x02 = interpolate(y0, x0, y2, x2);
+/// Draws a square dot about `size` pixels wide centered on `center`, at least one pixel.
+/// Every pixel of it gets depth tested against `inv_z` like `draw_triangle` does.
+/// Returns how many pixels were written.
+fn draw_point(
+    center: Vec2,
+    inv_z: f64,
+    size: f64,
+    color: [u8; 3],
+    frame: &mut [u8],
+    depth_buffer: &mut [f64],
+) -> usize {
+    // Wider than the canvas covers it all anyway.
+    let width = size.min(CANVAS_SIZE as f64).round().max(1.) as i32;
+    let x0 = (center.x - width as f64 / 2.).round() as i32;
+    let y0 = (center.y - width as f64 / 2.).round() as i32;
+    let half_size = CANVAS_SIZE as i32 / 2;
+    // Only walk the part of the square that lands on the canvas.
+    let xs = x0.max(-half_size)..x0.saturating_add(width).min(CANVAS_SIZE as i32 - half_size);
+    let ys =
+        y0.max(half_size + 1 - CANVAS_SIZE as i32)..y0.saturating_add(width).min(half_size + 1);
+    let mut written = 0;
+    for y in ys {
+        for x in xs.clone() {
+            let (x_to_draw, y_to_draw) = (x + half_size, -y + half_size);
+            let i = x_y_to_i(x_to_draw as u32, y_to_draw as u32);
+            if inv_z <= depth_buffer[i] {
+                continue;
+            }
+            depth_buffer[i] = inv_z;
+            put_pixel(frame, i, color);
+            written += 1;
+        }
+    }
+    written
+}
 
-    x01.remove(x01.len() - 1);
-    x01.append(&mut x12);
-    let x012 = x01;
+/// Walks the pixels covered by a triangle on a `size` x `size` target centered on the
+/// origin, calling `fragment` with the pixel index, the interpolated z and the
+/// interpolated `attrs`. The z component of `points` is interpolated linearly in
+/// screen space and the attributes are divided by it, so pass 1/z for perspective
+/// correct attributes.
+fn rasterize<V: Varyings>(
+    points: [Vec3; 3],
+    attrs: [V; 3],
+    size: u32,
+    mut fragment: impl FnMut(usize, f64, V),
+) {
+    // attr/z interpolates linearly in screen space, attr itself does not.
+    let mut verts = [0, 1, 2].map(|i| (points[i], attrs[i].scale(points[i].z)));
+    verts.sort_by(|a, b| a.0.y.total_cmp(&b.0.y));
+    let [(p0, a0), (p1, a1), (p2, a2)] = verts;
+    let (y0, y1, y2) = (p0.y, p1.y, p2.y);
+
+    let (x012, x02) = edge_interpolate(y0, p0.x, y1, p1.x, y2, p2.x);
+    let (z012, z02) = edge_interpolate(y0, p0.z, y1, p1.z, y2, p2.z);
+    let (attrs012, attrs02) = edge_interpolate(y0, a0, y1, a1, y2, a2);
 
     let m = x02.len() / 2;
-    let (mut x_left, mut x_right) = (&x012, &x02);
+    let mut left = (&x012, &z012, &attrs012);
+    let mut right = (&x02, &z02, &attrs02);
     if x012[m] > x02[m] {
-        (x_left, x_right) = (&x02, &x012);
+        swap(&mut left, &mut right);
     }
 
-    for y in y0 as i32..y2 as i32 {
-        let y_to_draw = -y + CANVAS_SIZE as i32 / 2;
+    let half_size = size as i32 / 2;
+    for y in (y0 as i32).max(-half_size)..(y2 as i32).min(half_size) {
+        let y_to_draw = -y + half_size;
         let y_index = (y as f64 - y0) as usize;
+        let (xl, xr) = (left.0[y_index], right.0[y_index]);
+        let z_segment = interpolate(xl, left.1[y_index], xr, right.1[y_index]);
+        let attr_segment = interpolate(xl, left.2[y_index], xr, right.2[y_index]);
 
-        for x in x_left[y_index] as i32..x_right[y_index] as i32 {
-            let x_to_draw = x + CANVAS_SIZE as i32 / 2;
+        for x in xl as i32..xr as i32 {
+            let x_to_draw = x + half_size;
 
-            if check_if_out_of_canvas(x_to_draw, y_to_draw) {
+            if x_to_draw < 0
+                || y_to_draw < 0
+                || x_to_draw >= size as i32
+                || y_to_draw >= size as i32
+            {
                 continue;
             }
 
-            let x_y_to_i = x_y_to_i(x_to_draw as u32, y_to_draw as u32) * 4;
-            frame[x_y_to_i] = color[0];
-            frame[x_y_to_i + 1] = color[1];
-            frame[x_y_to_i + 2] = color[2];
-            frame[x_y_to_i + 3] = 0xff;
+            let i = y_to_draw as usize * size as usize + x_to_draw as usize;
+            let x_index = ((x - xl as i32) as usize).min(z_segment.len() - 1);
+            let z = z_segment[x_index];
+            fragment(i, z, attr_segment[x_index].scale(1. / z));
         }
     }
 }
 
+/// Interpolates a value along the short edges 0-1-2 and the long edge 0-2 of a triangle
+/// sorted by y. Returns (short edges, long edge), both with one value per scanline.
+fn edge_interpolate<V: Varyings>(
+    y0: f64,
+    v0: V,
+    y1: f64,
+    v1: V,
+    y2: f64,
+    v2: V,
+) -> (Vec<V>, Vec<V>) {
+    let mut v01 = interpolate(y0, v0, y1, v1);
+    let mut v12 = interpolate(y1, v1, y2, v2);
+    let v02 = interpolate(y0, v0, y2, v2);
+
+    v01.remove(v01.len() - 1);
+    v01.append(&mut v12);
+    (v01, v02)
+}
+
+fn put_pixel(frame: &mut [u8], i: usize, color: [u8; 3]) {
+    let i = i * 4;
+    frame[i] = color[0];
+    frame[i + 1] = color[1];
+    frame[i + 2] = color[2];
+    frame[i + 3] = 0xff;
+}
+
 fn show_individual_pixels(frame: &mut [u8]) {
     let mut pog = true;
     for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
@@ -228,15 +546,36 @@ fn show_individual_pixels(frame: &mut [u8]) {
     }
 }
 
-fn draw_line(mut p0: Vec2, mut p1: Vec2, frame: &mut [u8], color: [u8; 3]) {
+fn draw_line(p0: Vec2, p1: Vec2, frame: &mut [u8], color: [u8; 3]) {
+    draw_shaded_line(p0, p1, frame, [color, color]);
+}
+
+/// Draws a line whose color blends from `colors[0]` at `p0` to `colors[1]` at `p1`.
+fn draw_shaded_line(p0: Vec2, p1: Vec2, frame: &mut [u8], colors: [[u8; 3]; 2]) {
+    let to_f64 = |c: [u8; 3]| c.map(|c| c as f64);
+    walk_line(p0, p1, colors.map(to_f64), |i, color| {
+        put_pixel(frame, i, color.map(|c| c as u8))
+    });
+}
+
+/// Walks the pixels of the line from `p0` to `p1` that are on the canvas, calling
+/// `plot` with the pixel index and `attrs` interpolated linearly along the line.
+fn walk_line<V: Varyings>(
+    mut p0: Vec2,
+    mut p1: Vec2,
+    mut attrs: [V; 2],
+    mut plot: impl FnMut(usize, V),
+) {
     if (p1.x - p0.x).abs() > (p1.y - p0.y).abs() {
         if p0.x > p1.x {
             swap(&mut p0, &mut p1);
+            attrs.swap(0, 1);
         };
 
         let (x0, x1, y0, y1) = (p0.x, p1.x, p0.y, p1.y);
 
         let ys = interpolate(x0, y0, x1, y1);
+        let values = interpolate(x0, attrs[0], x1, attrs[1]);
 
         for x in (x0 as i32)..(x1 as i32 + 1) {
             let x_to_draw = x + CANVAS_SIZE as i32 / 2;
@@ -245,20 +584,19 @@ fn draw_line(mut p0: Vec2, mut p1: Vec2, frame: &mut [u8], color: [u8; 3]) {
                 continue;
             }
 
-            let x_y_to_i = x_y_to_i(x_to_draw as u32, y_to_draw as u32) * 4;
-            frame[x_y_to_i] = color[0];
-            frame[x_y_to_i + 1] = color[1];
-            frame[x_y_to_i + 2] = color[2];
-            frame[x_y_to_i + 3] = 0xff;
+            let value = values[(x - x0 as i32) as usize];
+            plot(x_y_to_i(x_to_draw as u32, y_to_draw as u32), value);
         }
     } else {
         if p0.y > p1.y {
             swap(&mut p0, &mut p1);
+            attrs.swap(0, 1);
         };
 
         let (x0, x1, y0, y1) = (p0.x, p1.x, p0.y, p1.y);
 
         let xs = interpolate(y0, x0, y1, x1);
+        let values = interpolate(y0, attrs[0], y1, attrs[1]);
 
         for y in (y0 as i32)..(y1 as i32 + 1) {
             let x_to_draw = (xs[(y - y0 as i32) as usize] + CANVAS_SIZE as f64 / 2.) as i32;
@@ -267,11 +605,8 @@ fn draw_line(mut p0: Vec2, mut p1: Vec2, frame: &mut [u8], color: [u8; 3]) {
                 continue;
             }
 
-            let x_y_to_i = x_y_to_i(x_to_draw as u32, y_to_draw as u32) * 4;
-            frame[x_y_to_i] = color[0];
-            frame[x_y_to_i + 1] = color[1];
-            frame[x_y_to_i + 2] = color[2];
-            frame[x_y_to_i + 3] = 0xff;
+            let value = values[(y - y0 as i32) as usize];
+            plot(x_y_to_i(x_to_draw as u32, y_to_draw as u32), value);
         }
     }
 }
@@ -286,133 +621,25 @@ fn draw_wireframe_triangle(p0: Vec2, p1: Vec2, p2: Vec2, frame: &mut [u8], color
     draw_line(p2, p0, frame, color);
 }
 
-fn interpolate(i0: f64, d0: f64, i1: f64, d1: f64) -> Vec<f64> {
+fn interpolate<V: Varyings>(i0: f64, d0: V, i1: f64, d1: V) -> Vec<V> {
     if i0 as i32 == i1 as i32 {
         return vec![d0];
     }
-    let a = (d1 - d0) / (i1 - i0);
+    let a = d1.add(d0.scale(-1.)).scale(1. / (i1 - i0));
     let mut d = d0;
 
     let mut values = Vec::new();
     for _ in i0 as i32..i1 as i32 + 1 {
         values.push(d);
-        d += a;
+        d = d.add(a);
     }
     values
 }
 
 fn swap<T: std::marker::Copy>(x0: &mut T, x1: &mut T) {
-    let temp = *x0;
-    *x0 = *x1;
-    *x1 = temp;
+    std::mem::swap(x0, x1);
 }
 
 fn check_if_out_of_canvas(x: i32, y: i32) -> bool {
     x < 0 || y < 0 || x >= CANVAS_SIZE as i32 || y >= CANVAS_SIZE as i32
 }
-
-fn clear_screen(frame: &mut [u8]) {
-    for pixel in frame.chunks_exact_mut(100) {
-        pixel[0] = 0x00; // R
-        pixel[1] = 0x00; // G
-        pixel[2] = 0x00; // B
-
-        pixel[4] = 0x00; // R
-        pixel[5] = 0x00; // G
-        pixel[6] = 0x00; // B
-
-        pixel[8] = 0x00; // R
-        pixel[9] = 0x00; // G
-        pixel[10] = 0x00; // B
-
-        pixel[12] = 0x00; // R
-        pixel[13] = 0x00; // G
-        pixel[14] = 0x00; // B
-
-        pixel[16] = 0x00; // R
-        pixel[17] = 0x00; // G
-        pixel[18] = 0x00; // B
-
-        pixel[20] = 0x00; // R
-        pixel[21] = 0x00; // G
-        pixel[22] = 0x00; // B
-
-        pixel[24] = 0x00; // R
-        pixel[25] = 0x00; // G
-        pixel[26] = 0x00; // B
-
-        pixel[28] = 0x00; // R
-        pixel[29] = 0x00; // G
-        pixel[30] = 0x00; // B
-
-        pixel[32] = 0x00; // R
-        pixel[33] = 0x00; // G
-        pixel[34] = 0x00; // B
-
-        pixel[36] = 0x00; // R
-        pixel[37] = 0x00; // G
-        pixel[38] = 0x00; // B
-
-        pixel[40] = 0x00; // G
-        pixel[41] = 0x00; // B
-        pixel[42] = 0x00; // B
-
-        pixel[44] = 0x00; // G
-        pixel[45] = 0x00; // B
-        pixel[46] = 0x00; // B
-
-        pixel[48] = 0x00; // G
-        pixel[49] = 0x00; // B
-        pixel[50] = 0x00; // B
-
-        pixel[52] = 0x00; // G
-        pixel[53] = 0x00; // B
-        pixel[54] = 0x00; // B
-
-        pixel[56] = 0x00; // R
-        pixel[57] = 0x00; // G
-        pixel[58] = 0x00; // B
-
-        pixel[60] = 0x00; // R
-        pixel[61] = 0x00; // G
-        pixel[62] = 0x00; // B
-
-        pixel[64] = 0x00; // G
-        pixel[65] = 0x00; // B
-        pixel[66] = 0x00; // B
-
-        pixel[68] = 0x00; // G
-        pixel[69] = 0x00; // B
-        pixel[70] = 0x00; // B
-
-        pixel[72] = 0x00; // G
-        pixel[73] = 0x00; // B
-        pixel[74] = 0x00; // B
-
-        pixel[76] = 0x00; // G
-        pixel[77] = 0x00; // B
-        pixel[78] = 0x00; // B
-
-        pixel[80] = 0x00; // G
-        pixel[81] = 0x00; // B
-        pixel[82] = 0x00; // B
-
-        pixel[84] = 0x00; // G
-        pixel[85] = 0x00; // B
-        pixel[86] = 0x00; // B
-
-        pixel[88] = 0x00; // G
-        pixel[89] = 0x00; // B
-        pixel[90] = 0x00; // B
-
-        pixel[92] = 0x00; // G
-        pixel[93] = 0x00; // B
-        pixel[94] = 0x00; // B
-
-        pixel[96] = 0x00; // G
-        pixel[97] = 0x00; // B
-        pixel[98] = 0x00; // B
-
-        pixel[99] = 0x00; // G
-    }
-}
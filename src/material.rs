@@ -0,0 +1,159 @@
+use crate::{consts::*, shader::CustomShader};
+use serde::Deserialize;
+use std::{ops::Range, path::Path, rc::Rc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum RenderMode {
+    Wireframe,
+    Filled,
+    /// Lit once per triangle, at its center.
+    Shaded,
+    /// Lit per pixel.
+    Phong,
+    /// Only the vertices, each drawn as a dot. Meant for point clouds without faces.
+    Points,
+}
+
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub base_color: [u8; 3],
+    /// Phong specular exponent, -1 for a matte surface.
+    pub specular: f64,
+    /// How much of the environment the surface mirrors, 0 to 1. Only used by
+    /// `RenderMode::Phong`, which has a normal for every pixel.
+    pub reflectivity: f64,
+    pub texture: Option<Rc<Texture>>,
+    /// Tangent space normals, only used by `RenderMode::Phong`.
+    pub normal_map: Option<Rc<Texture>>,
+    pub mode: RenderMode,
+    /// Dot size in pixels for `RenderMode::Points`.
+    pub point_size: f64,
+    /// View distance at which dots are `point_size` wide, nearer ones growing and
+    /// farther ones shrinking. Dots keep the same size at any distance if `None`.
+    pub point_distance: Option<f64>,
+    /// Replaces the built-in pipeline picked by `mode` when set.
+    pub shader: Option<CustomShader>,
+}
+impl Material {
+    pub fn new(base_color: [u8; 3], mode: RenderMode) -> Self {
+        Self {
+            base_color,
+            specular: -1.,
+            reflectivity: 0.,
+            texture: None,
+            normal_map: None,
+            mode,
+            point_size: DEFAULT_POINT_SIZE,
+            point_distance: None,
+            shader: None,
+        }
+    }
+    pub fn with_specular(mut self, specular: f64) -> Self {
+        self.specular = specular;
+        self
+    }
+    pub fn with_reflectivity(mut self, reflectivity: f64) -> Self {
+        self.reflectivity = reflectivity;
+        self
+    }
+    pub fn with_texture(mut self, texture: Rc<Texture>) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+    pub fn with_normal_map(mut self, normal_map: Rc<Texture>) -> Self {
+        self.normal_map = Some(normal_map);
+        self
+    }
+    pub fn with_point_size(mut self, point_size: f64, point_distance: Option<f64>) -> Self {
+        self.point_size = point_size;
+        self.point_distance = point_distance;
+        self
+    }
+    pub fn with_shader(mut self, shader: CustomShader) -> Self {
+        self.shader = Some(shader);
+        self
+    }
+}
+impl Default for Material {
+    fn default() -> Self {
+        Material::new(WHITE, RenderMode::Wireframe)
+    }
+}
+
+/// Overrides the instance material for the triangles in `tris`.
+#[derive(Debug, Clone)]
+pub struct MaterialRange {
+    pub tris: Range<usize>,
+    pub material: Material,
+}
+
+#[derive(Debug, Clone)]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    texels: Vec<[u8; 3]>,
+}
+impl Texture {
+    pub fn new(width: usize, height: usize, texels: Vec<[u8; 3]>) -> Self {
+        assert_eq!(texels.len(), width * height);
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.into_rgb8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let texels = image.pixels().map(|p| p.0).collect();
+        Ok(Texture::new(width, height, texels))
+    }
+
+    pub fn checkerboard(size: usize, squares: usize, c0: [u8; 3], c1: [u8; 3]) -> Self {
+        let square_size = (size / squares).max(1);
+        let mut texels = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                if (x / square_size + y / square_size).is_multiple_of(2) {
+                    texels.push(c0);
+                } else {
+                    texels.push(c1);
+                }
+            }
+        }
+        Texture::new(size, size, texels)
+    }
+
+    /// Decodes the texel at (u, v) of a normal map into a tangent space normal.
+    pub fn sample_normal(&self, u: f64, v: f64) -> Vec3 {
+        let texel = self.sample(u, v);
+        Vec3::new(
+            texel[0] as f64 / 255. * 2. - 1.,
+            texel[1] as f64 / 255. * 2. - 1.,
+            texel[2] as f64 / 255. * 2. - 1.,
+        )
+    }
+
+    /// Nearest-texel lookup, wrapping uv coordinates outside of [0, 1].
+    pub fn sample(&self, u: f64, v: f64) -> [u8; 3] {
+        let u = u - u.floor();
+        let v = v - v.floor();
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.texels[y * self.width + x]
+    }
+}
+
+/// Linear blend, `t` = 0 gives `a` and 1 gives `b`.
+pub fn blend_colors(a: [u8; 3], b: [u8; 3], t: f64) -> [u8; 3] {
+    std::array::from_fn(|c| (a[c] as f64 * (1. - t) + b[c] as f64 * t) as u8)
+}
+
+pub fn shade_color(color: [u8; 3], intensity: f64) -> [u8; 3] {
+    [
+        (color[0] as f64 * intensity).min(255.) as u8,
+        (color[1] as f64 * intensity).min(255.) as u8,
+        (color[2] as f64 * intensity).min(255.) as u8,
+    ]
+}
@@ -0,0 +1,426 @@
+use crate::{consts::*, skeleton::JointWeights};
+use cgmath::*;
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    pub verts: Vertices,
+    pub triangles: Vec<Indices>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Option<Vec<Vec2>>,
+    /// Tangent in xyz with the bitangent's handedness in w, see `generate_tangents`.
+    pub tangents: Option<Vec<Vec4>>,
+    /// Joints moving each vertex when the mesh is skinned.
+    pub joint_weights: Option<Vec<JointWeights>>,
+    /// Per-vertex colors, used in place of the material color when it has no texture.
+    pub colors: Option<Vec<[u8; 3]>>,
+}
+impl Mesh {
+    /// Builds a mesh and generates its normals using `DEFAULT_CREASE_ANGLE`.
+    pub fn new(verts: Vertices, triangles: Vec<Indices>, uvs: Option<Vec<Vec2>>) -> Self {
+        let mut mesh = Self {
+            verts,
+            triangles,
+            normals: Vec::new(),
+            uvs,
+            tangents: None,
+            joint_weights: None,
+            colors: None,
+        };
+        mesh.generate_normals(DEFAULT_CREASE_ANGLE);
+        mesh.generate_tangents();
+        mesh
+    }
+
+    pub fn with_normals(
+        verts: Vertices,
+        triangles: Vec<Indices>,
+        normals: Vec<Vec3>,
+        uvs: Option<Vec<Vec2>>,
+    ) -> Self {
+        assert_eq!(verts.len(), normals.len());
+        let mut mesh = Self {
+            verts,
+            triangles,
+            normals,
+            uvs,
+            tangents: None,
+            joint_weights: None,
+            colors: None,
+        };
+        mesh.generate_tangents();
+        mesh
+    }
+
+    pub fn with_colors(mut self, colors: Vec<[u8; 3]>) -> Self {
+        assert_eq!(self.verts.len(), colors.len());
+        self.colors = Some(colors);
+        self
+    }
+
+    pub fn with_joint_weights(mut self, joint_weights: Vec<JointWeights>) -> Self {
+        assert_eq!(self.verts.len(), joint_weights.len());
+        self.joint_weights = Some(joint_weights);
+        self
+    }
+
+    /// Replaces the normals by area weighted averages of the adjacent face normals.
+    /// Faces meeting at more than `crease_angle` degrees don't smooth into each other;
+    /// their shared vertices get split so the edge stays hard.
+    pub fn generate_normals(&mut self, crease_angle: f64) {
+        let cos_crease = Deg(crease_angle).cos();
+
+        // The cross product's length is twice the triangle area, so summing the raw
+        // cross products weights every face by its area.
+        let face_normals: Vec<Vec3> = self
+            .triangles
+            .iter()
+            .map(|t| (self.verts[t.1] - self.verts[t.0]).cross(self.verts[t.2] - self.verts[t.0]))
+            .collect();
+
+        // Vertices that were already split (e.g. along uv seams) still share a position.
+        let mut faces_at_pos: HashMap<[u64; 3], Vec<usize>> = HashMap::new();
+        for (face, tri) in self.triangles.iter().enumerate() {
+            for v in [tri.0, tri.1, tri.2] {
+                faces_at_pos
+                    .entry(pos_key(self.verts[v]))
+                    .or_default()
+                    .push(face);
+            }
+        }
+
+        let mut verts = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = self.uvs.as_ref().map(|_| Vec::new());
+        let mut joint_weights = self.joint_weights.as_ref().map(|_| Vec::new());
+        let mut colors = self.colors.as_ref().map(|_| Vec::new());
+        let mut split_verts: HashMap<(usize, [u64; 3]), usize> = HashMap::new();
+        let mut triangles = Vec::with_capacity(self.triangles.len());
+
+        for (face, tri) in self.triangles.iter().enumerate() {
+            let face_dir = normalize_or_zero(face_normals[face]);
+            let mut new_tri = [0; 3];
+
+            for (corner, v) in [tri.0, tri.1, tri.2].into_iter().enumerate() {
+                let mut normal = Vec3::zero();
+                for &other in &faces_at_pos[&pos_key(self.verts[v])] {
+                    if normalize_or_zero(face_normals[other]).dot(face_dir) >= cos_crease {
+                        normal += face_normals[other];
+                    }
+                }
+                let normal = normalize_or_zero(normal);
+
+                new_tri[corner] = *split_verts.entry((v, pos_key(normal))).or_insert_with(|| {
+                    verts.push(self.verts[v]);
+                    normals.push(normal);
+                    if let (Some(uvs), Some(old_uvs)) = (&mut uvs, &self.uvs) {
+                        uvs.push(old_uvs[v]);
+                    }
+                    if let (Some(weights), Some(old_weights)) =
+                        (&mut joint_weights, &self.joint_weights)
+                    {
+                        weights.push(old_weights[v]);
+                    }
+                    if let (Some(colors), Some(old_colors)) = (&mut colors, &self.colors) {
+                        colors.push(old_colors[v]);
+                    }
+                    verts.len() - 1
+                });
+            }
+            triangles.push((new_tri[0], new_tri[1], new_tri[2]));
+        }
+
+        self.verts = verts;
+        self.normals = normals;
+        self.uvs = uvs;
+        self.joint_weights = joint_weights;
+        self.colors = colors;
+        self.triangles = triangles;
+    }
+
+    /// Computes per-vertex tangents from the uv layout, needed for normal mapping.
+    /// Like MikkTSpace, the uv derivatives of all adjacent triangles are accumulated,
+    /// then orthogonalized against the normal. `w` is the sign that turns
+    /// `normal.cross(tangent)` into the bitangent pointing up the texture image, which
+    /// is where the green channel of a normal map points. Does nothing without uvs, so
+    /// call it again after changing a mesh's vertices by hand.
+    pub fn generate_tangents(&mut self) {
+        let uvs = match &self.uvs {
+            Some(uvs) => uvs,
+            None => {
+                self.tangents = None;
+                return;
+            }
+        };
+        let mut du = vec![Vec3::zero(); self.verts.len()];
+        let mut dv = vec![Vec3::zero(); self.verts.len()];
+
+        for tri in &self.triangles {
+            let (p0, p1, p2) = (self.verts[tri.0], self.verts[tri.1], self.verts[tri.2]);
+            let (uv0, uv1, uv2) = (uvs[tri.0], uvs[tri.1], uvs[tri.2]);
+            let (e1, e2) = (p1 - p0, p2 - p0);
+            let (duv1, duv2) = (uv1 - uv0, uv2 - uv0);
+
+            let det = duv1.x * duv2.y - duv2.x * duv1.y;
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            let dp_du = (e1 * duv2.y - e2 * duv1.y) / det;
+            let dp_dv = (e2 * duv1.x - e1 * duv2.x) / det;
+            for v in [tri.0, tri.1, tri.2] {
+                du[v] += dp_du;
+                dv[v] += dp_dv;
+            }
+        }
+
+        let tangents = (0..self.verts.len())
+            .map(|v| {
+                let normal = self.normals[v];
+                let mut tangent = du[v] - normal * normal.dot(du[v]);
+                if tangent.magnitude2() < 1e-12 {
+                    // No usable uv gradient, any direction along the surface will do.
+                    tangent = normal.cross(Vec3::unit_y());
+                    if tangent.magnitude2() < 1e-12 {
+                        tangent = normal.cross(Vec3::unit_x());
+                    }
+                }
+                let tangent = normalize_or_zero(tangent);
+                // v grows downwards in the image, so up the image is -dP/dv.
+                let w = if normal.cross(tangent).dot(-dv[v]) < 0. {
+                    -1.
+                } else {
+                    1.
+                };
+                tangent.extend(w)
+            })
+            .collect();
+        self.tangents = Some(tangents);
+    }
+
+    /// Loads an OBJ, STL or PLY file, going by the extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh, MeshError> {
+        let path = path.as_ref();
+        Mesh::load_as(path, MeshFormat::from_path(path))
+    }
+
+    pub fn load_as<P: AsRef<Path>>(path: P, format: MeshFormat) -> Result<Mesh, MeshError> {
+        match format {
+            MeshFormat::Obj => Mesh::load_obj(path),
+            MeshFormat::Stl => Mesh::load_stl(path),
+            MeshFormat::Ply => Mesh::load_ply(path),
+        }
+    }
+
+    /// Loads a Wavefront OBJ file. Polygons are triangulated as fans. Normals are
+    /// taken from the file when every face references one, otherwise they're generated.
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Mesh, MeshError> {
+        let source = fs::read_to_string(path)?;
+        Mesh::parse_obj(&source)
+    }
+
+    pub fn parse_obj(source: &str) -> Result<Mesh, MeshError> {
+        let mut positions = Vec::new();
+        let mut tex_coords = Vec::new();
+        let mut file_normals = Vec::new();
+
+        let mut verts = Vec::new();
+        let mut uvs = Vec::new();
+        let mut normals = Vec::new();
+        let mut triangles = Vec::new();
+        let mut corner_to_vert: HashMap<(usize, Option<usize>, Option<usize>), usize> =
+            HashMap::new();
+        let (mut all_have_uvs, mut all_have_normals) = (true, true);
+
+        for (line_index, line) in source.lines().enumerate() {
+            let line_nr = line_index + 1;
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => positions.push(parse_vec3(words, line_nr)?),
+                Some("vn") => file_normals.push(parse_vec3(words, line_nr)?),
+                Some("vt") => {
+                    let u = parse_f64(words.next(), line_nr)?;
+                    let v = parse_f64(words.next(), line_nr)?;
+                    // OBJ puts v = 0 at the bottom of the image, textures here start at the top.
+                    tex_coords.push(Vec2::new(u, 1. - v));
+                }
+                Some("f") => {
+                    let mut face = Vec::new();
+                    for corner in words {
+                        let mut parts = corner.split('/');
+                        let v = parse_index(parts.next(), positions.len(), line_nr)?;
+                        let vt = parse_optional_index(parts.next(), tex_coords.len(), line_nr)?;
+                        let vn = parse_optional_index(parts.next(), file_normals.len(), line_nr)?;
+                        all_have_uvs &= vt.is_some();
+                        all_have_normals &= vn.is_some();
+
+                        let index = *corner_to_vert.entry((v, vt, vn)).or_insert_with(|| {
+                            verts.push(positions[v]);
+                            uvs.push(vt.map_or(Vec2::zero(), |vt| tex_coords[vt]));
+                            normals.push(vn.map_or(Vec3::zero(), |vn| file_normals[vn]));
+                            verts.len() - 1
+                        });
+                        face.push(index);
+                    }
+                    if face.len() < 3 {
+                        return Err(MeshError::Parse {
+                            line: line_nr,
+                            msg: "a face needs at least 3 vertices".to_string(),
+                        });
+                    }
+                    for i in 1..face.len() - 1 {
+                        triangles.push((face[0], face[i], face[i + 1]));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // Vertices only come from face corners.
+        if verts.is_empty() {
+            return Err(MeshError::Invalid("the file has no faces".to_string()));
+        }
+        let uvs = if all_have_uvs { Some(uvs) } else { None };
+        if all_have_normals {
+            Ok(Mesh::with_normals(verts, triangles, normals, uvs))
+        } else {
+            Ok(Mesh::new(verts, triangles, uvs))
+        }
+    }
+}
+
+pub fn normalize_or_zero(v: Vec3) -> Vec3 {
+    if v.magnitude2() == 0. {
+        v
+    } else {
+        v.normalize()
+    }
+}
+
+/// Exact bit pattern of a vector, used to find vertices that share a position.
+pub fn pos_key(v: Vec3) -> [u64; 3] {
+    // +0.0 and -0.0 compare equal but have different bits.
+    [
+        (v.x + 0.).to_bits(),
+        (v.y + 0.).to_bits(),
+        (v.z + 0.).to_bits(),
+    ]
+}
+
+fn parse_f64(word: Option<&str>, line: usize) -> Result<f64, MeshError> {
+    let word = word.ok_or_else(|| MeshError::Parse {
+        line,
+        msg: "expected a number".to_string(),
+    })?;
+    word.parse().map_err(|_| MeshError::Parse {
+        line,
+        msg: format!("'{}' is not a number", word),
+    })
+}
+
+fn parse_vec3<'a>(
+    mut words: impl Iterator<Item = &'a str>,
+    line: usize,
+) -> Result<Vec3, MeshError> {
+    Ok(Vec3::new(
+        parse_f64(words.next(), line)?,
+        parse_f64(words.next(), line)?,
+        parse_f64(words.next(), line)?,
+    ))
+}
+
+/// OBJ indices start at 1, negative ones count back from the last element read so far.
+fn parse_index(word: Option<&str>, count: usize, line: usize) -> Result<usize, MeshError> {
+    let word = word.unwrap_or("");
+    let index: i64 = word.parse().map_err(|_| MeshError::Parse {
+        line,
+        msg: format!("'{}' is not a valid index", word),
+    })?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(MeshError::Parse {
+            line,
+            msg: format!("index {} is out of range", index),
+        });
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_optional_index(
+    word: Option<&str>,
+    count: usize,
+    line: usize,
+) -> Result<Option<usize>, MeshError> {
+    match word {
+        None | Some("") => Ok(None),
+        Some(_) => parse_index(word, count, line).map(Some),
+    }
+}
+
+/// File formats meshes can be loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshFormat {
+    Obj,
+    Stl,
+    Ply,
+}
+impl MeshFormat {
+    /// Goes by the file extension, taking anything but .stl and .ply for OBJ.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match extension.to_ascii_lowercase().as_str() {
+            "stl" => MeshFormat::Stl,
+            "ply" => MeshFormat::Ply,
+            _ => MeshFormat::Obj,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        msg: String,
+    },
+    /// Problems in binary files, which have no lines to point at.
+    Invalid(String),
+}
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::Io(e) => write!(f, "{}", e),
+            MeshError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+            MeshError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+impl std::error::Error for MeshError {}
+impl From<io::Error> for MeshError {
+    fn from(e: io::Error) -> Self {
+        MeshError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obj_without_faces() {
+        for source in ["", "# nothing yet\n", "v 0 0 0\nv 1 0 0\nv 0 1 0\n"] {
+            let error = Mesh::parse_obj(source).unwrap_err();
+            assert_eq!(error.to_string(), "the file has no faces");
+        }
+    }
+
+    #[test]
+    fn obj_triangle() {
+        let mesh = Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(mesh.verts.len(), 3);
+        assert_eq!(mesh.triangles, vec![(0, 1, 2)]);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    clipping::{clip_segment_to_near, clip_triangle_to_near},
+    consts::*,
+    instance::{is_back_facing, project_vertex, with_inv_z, Instance, Transform},
+    put_pixel, rasterize, walk_line,
+};
+
+/// Pixels covered by the instances, whether or not something else is in front of them.
+/// Works like a stencil buffer the selection gets drawn into.
+pub fn coverage_mask(instances: &[&Instance], cam_trans: Transform) -> Vec<bool> {
+    let mut mask = vec![false; (CANVAS_SIZE * CANVAS_SIZE) as usize];
+    for instance in instances {
+        let transformed = instance.camera_space_verts(cam_trans);
+        for tri in &instance.mesh.triangles {
+            let corners = [tri.0, tri.1, tri.2].map(|v| transformed[v]);
+            for clipped in clip_triangle_to_near(corners) {
+                let points = clipped.map(|v| with_inv_z(project_vertex(v), v));
+                rasterize(points, [[]; 3], CANVAS_SIZE, |i, _, _| mask[i] = true);
+            }
+        }
+    }
+    mask
+}
+
+/// Paints the pixels outside `mask` that are at most `width` pixels from it, so the
+/// masked shape gets a border drawn over whatever surrounds it.
+pub fn draw_outline(frame: &mut [u8], mask: &[bool], width: i32, color: [u8; 3]) {
+    let size = CANVAS_SIZE as i32;
+    let covered = |x: i32, y: i32| {
+        (0..size).contains(&x) && (0..size).contains(&y) && mask[(y * size + x) as usize]
+    };
+    for y in 0..size {
+        for x in 0..size {
+            // Only pixels on the edge of the mask can have uncovered pixels nearby.
+            let on_edge = covered(x, y)
+                && !(covered(x - 1, y)
+                    && covered(x + 1, y)
+                    && covered(x, y - 1)
+                    && covered(x, y + 1));
+            if !on_edge {
+                continue;
+            }
+            for ny in (y - width).max(0)..(y + width + 1).min(size) {
+                for nx in (x - width).max(0)..(x + width + 1).min(size) {
+                    if !covered(nx, ny) {
+                        put_pixel(frame, (ny * size + nx) as usize, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draws the edges of the instance's front faces over the frame, hidden where the
+/// depth buffer has something in front of them. `OVERLAY_DEPTH_BIAS` keeps the edges
+/// from losing the depth test against the faces they lie on.
+pub fn draw_wireframe_overlay(
+    frame: &mut [u8],
+    depth_buffer: &[f64],
+    instance: &Instance,
+    cam_trans: Transform,
+    color: [u8; 3],
+) {
+    let transformed = instance.camera_space_verts(cam_trans);
+    for tri in &instance.mesh.triangles {
+        let corners = [tri.0, tri.1, tri.2].map(|v| transformed[v]);
+        if is_back_facing(corners) {
+            continue;
+        }
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            let Some((v0, v1)) = clip_segment_to_near(corners[a], corners[b]) else {
+                continue;
+            };
+            // 1/z interpolates linearly in screen space.
+            let inv_z = [1. / v0.z, 1. / v1.z];
+            walk_line(project_vertex(v0), project_vertex(v1), inv_z, |i, inv_z| {
+                if inv_z * (1. + OVERLAY_DEPTH_BIAS) >= depth_buffer[i] {
+                    put_pixel(frame, i, color);
+                }
+            });
+        }
+    }
+}
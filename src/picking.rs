@@ -0,0 +1,220 @@
+use crate::{
+    bvh::Bvh,
+    clipping::{Aabb, BoundingSphere},
+    consts::*,
+    instance::{canvas_to_viewport, Instance, Transform},
+};
+use cgmath::*;
+
+/// Rays closer to parallel with a triangle than this miss it.
+const PARALLEL_EPSILON: f64 = 1e-12;
+
+/// A half-line in camera space.
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub dir: Vec3,
+}
+impl Ray {
+    /// The ray from the eye through a canvas point, undoing `project_vertex`.
+    pub fn through_canvas(point: Vec2) -> Self {
+        let viewport = canvas_to_viewport(point.x, point.y);
+        Self {
+            origin: Vec3::zero(),
+            dir: Vec3::new(viewport.x, viewport.y, D),
+        }
+    }
+
+    /// Moves a camera space ray into world space.
+    pub fn to_world(self, cam_trans: Transform) -> Self {
+        Self {
+            origin: cam_trans.rotate_from_camera(self.origin)
+                - cam_trans.translation
+                - DEFAULT_TRANSL,
+            dir: cam_trans.rotate_from_camera(self.dir),
+        }
+    }
+
+    /// Where the ray crosses the plane through `point` facing `normal`, if it does in
+    /// front of its origin.
+    pub fn hit_plane(self, point: Vec3, normal: Vec3) -> Option<Vec3> {
+        let facing = self.dir.dot(normal);
+        if facing.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+        let t = (point - self.origin).dot(normal) / facing;
+        (t >= 0.).then(|| self.at(t))
+    }
+
+    /// The point on the line through `point` along `dir` that's closest to the ray.
+    pub fn closest_on_line(self, point: Vec3, dir: Vec3) -> Option<Vec3> {
+        let offset = point - self.origin;
+        let (a, b, c) = (dir.magnitude2(), dir.dot(self.dir), self.dir.magnitude2());
+        let denominator = a * c - b * b;
+        if denominator.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+        let s = (b * self.dir.dot(offset) - c * dir.dot(offset)) / denominator;
+        Some(point + dir * s)
+    }
+
+    pub fn at(self, t: f64) -> Vec3 {
+        self.origin + self.dir * t
+    }
+
+    /// Distance to where the ray enters the sphere, 0 if it starts inside it.
+    pub fn hit_sphere(self, sphere: BoundingSphere) -> Option<f64> {
+        let offset = self.origin - sphere.center;
+        let a = self.dir.magnitude2();
+        let b = offset.dot(self.dir);
+        let c = offset.magnitude2() - sphere.radius * sphere.radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0. {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (enter, exit) = ((-b - root) / a, (-b + root) / a);
+        if exit < 0. {
+            return None;
+        }
+        Some(enter.max(0.))
+    }
+
+    /// Distance to where the ray enters the box, 0 if it starts inside it. Uses the slab
+    /// test: the ray is inside the box where it's between all three pairs of faces.
+    pub fn hit_aabb(self, aabb: Aabb) -> Option<f64> {
+        let (mut enter, mut exit) = (0., f64::INFINITY);
+        for i in 0..3 {
+            let inv_dir = 1. / self.dir[i];
+            let near = (aabb.min[i] - self.origin[i]) * inv_dir;
+            let far = (aabb.max[i] - self.origin[i]) * inv_dir;
+            // NaN, from a ray along a face, is skipped by min and max.
+            enter = f64::max(enter, near.min(far));
+            exit = f64::min(exit, near.max(far));
+        }
+        (enter <= exit).then_some(enter)
+    }
+
+    /// Distance to the triangle and the weights of its corners at the hit, using the
+    /// Möller-Trumbore test. Both faces count.
+    pub fn hit_triangle(self, [v0, v1, v2]: [Vec3; 3]) -> Option<(f64, Vec3)> {
+        let (edge1, edge2) = (v1 - v0, v2 - v0);
+        let p = self.dir.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+        let inv_det = 1. / det;
+        let s = self.origin - v0;
+        let u = s.dot(p) * inv_det;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = self.dir.dot(q) * inv_det;
+        if v < 0. || u + v > 1. {
+            return None;
+        }
+        let t = edge2.dot(q) * inv_det;
+        if t < 0. {
+            return None;
+        }
+        Some((t, Vec3::new(1. - u - v, u, v)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    /// Index into the scene's instances.
+    pub instance: usize,
+    /// Index into the instance mesh's triangles.
+    pub triangle: usize,
+    /// Weights of the triangle's corners at the hit, summing to 1.
+    pub barycentric: Vec3,
+    /// Camera space position of the hit.
+    pub point: Vec3,
+    /// Distance along the ray, in units of its direction.
+    pub t: f64,
+}
+
+/// Canvas coordinates of the center of a pixel of the frame, y pointing up.
+pub fn pixel_to_canvas((x, y): (usize, usize)) -> Vec2 {
+    let half_size = CANVAS_SIZE as f64 / 2.;
+    Vec2::new(x as f64 + 0.5 - half_size, half_size - y as f64 - 0.5)
+}
+
+/// Nearest triangle under a canvas point. The BVH is walked nearer boxes first,
+/// skipping boxes the ray enters further away than the best hit so far. Like the
+/// renderer, it ignores whatever is in front of the near plane.
+pub fn pick(instances: &[Instance], bvh: &Bvh, cam_trans: Transform, point: Vec2) -> Option<Hit> {
+    let ray = Ray::through_canvas(point);
+    // Turning the ray into world space keeps distances along it the same.
+    let world_ray = ray.to_world(cam_trans);
+    let mut best: Option<Hit> = None;
+    let mut stack: Vec<(usize, f64)> = Vec::new();
+    if let Some(enter) = bvh
+        .nodes
+        .first()
+        .and_then(|root| world_ray.hit_aabb(root.bounds))
+    {
+        stack.push((0, enter));
+    }
+    while let Some((node, enter)) = stack.pop() {
+        if best.is_some_and(|best| best.t <= enter) {
+            continue;
+        }
+        let Some(children) = bvh.nodes[node].children else {
+            for &i in bvh.instances_of(node) {
+                pick_instance(instances, i, (ray, cam_trans), &mut best);
+            }
+            continue;
+        };
+        let mut hits: Vec<(usize, f64)> = children
+            .iter()
+            .filter_map(|&child| {
+                let enter = world_ray.hit_aabb(bvh.nodes[child].bounds)?;
+                Some((child, enter))
+            })
+            .collect();
+        // The nearer child goes on top.
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        stack.extend(hits);
+    }
+    best
+}
+
+/// Replaces `best` with the nearest hit on instance `i` if that's nearer.
+fn pick_instance(
+    instances: &[Instance],
+    i: usize,
+    (ray, cam_trans): (Ray, Transform),
+    best: &mut Option<Hit>,
+) {
+    let instance = &instances[i];
+    let sphere = instance
+        .bounding_sphere
+        .transformed(instance.trans)
+        .to_camera_space(cam_trans);
+    match ray.hit_sphere(sphere) {
+        Some(enter) if best.is_none_or(|best| enter < best.t) => (),
+        _ => return,
+    }
+    let transformed = instance.camera_space_verts(cam_trans);
+    for (triangle, tri) in instance.mesh.triangles.iter().enumerate() {
+        let corners = [tri.0, tri.1, tri.2].map(|v| transformed[v]);
+        let Some((t, barycentric)) = ray.hit_triangle(corners) else {
+            continue;
+        };
+        let hit_point = ray.at(t);
+        if hit_point.z < D || best.is_some_and(|best| best.t <= t) {
+            continue;
+        }
+        *best = Some(Hit {
+            instance: i,
+            triangle,
+            barycentric,
+            point: hit_point,
+            t,
+        });
+    }
+}
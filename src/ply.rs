@@ -0,0 +1,509 @@
+use crate::{
+    consts::*,
+    mesh::{Mesh, MeshError},
+};
+use cgmath::*;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Vertex properties the loader reads, each a single number.
+const VERTEX_SCALARS: [&str; 15] = [
+    "x",
+    "y",
+    "z",
+    "nx",
+    "ny",
+    "nz",
+    "s",
+    "t",
+    "u",
+    "v",
+    "texture_u",
+    "texture_v",
+    "red",
+    "green",
+    "blue",
+];
+
+impl Mesh {
+    /// Loads an ASCII or binary PLY file. Besides positions it reads normals (`nx`,
+    /// `ny`, `nz`), texture coordinates (`s`/`t`, `u`/`v` or `texture_u`/`texture_v`)
+    /// and colors (`red`, `green`, `blue`). Faces are triangulated as fans. A file
+    /// without faces becomes a point cloud, a mesh without triangles.
+    pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<Mesh, MeshError> {
+        let bytes = fs::read(path)?;
+        Mesh::parse_ply(&bytes)
+    }
+
+    pub fn parse_ply(bytes: &[u8]) -> Result<Mesh, MeshError> {
+        let (header, body) = parse_header(bytes)?;
+        let mut reader: Box<dyn ValueReader> = match header.format {
+            Format::Ascii => {
+                let body = std::str::from_utf8(body)
+                    .map_err(|_| MeshError::Invalid("the data isn't text".to_string()))?;
+                Box::new(AsciiReader {
+                    words: body.split_whitespace(),
+                })
+            }
+            Format::BinaryLittleEndian => Box::new(BinaryReader {
+                bytes: body,
+                big_endian: false,
+            }),
+            Format::BinaryBigEndian => Box::new(BinaryReader {
+                bytes: body,
+                big_endian: true,
+            }),
+        };
+
+        let mut verts = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut colors = Vec::new();
+        let mut triangles = Vec::new();
+        let (mut has_normals, mut has_uvs, mut has_colors) = (false, false, false);
+
+        for element in &header.elements {
+            match element.name.as_str() {
+                "vertex" => {
+                    let find = |names: &[&str]| {
+                        element
+                            .properties
+                            .iter()
+                            .position(|p| names.contains(&p.name.as_str()))
+                    };
+                    let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+                    let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+                    let uv = [
+                        find(&["s", "u", "texture_u"]),
+                        find(&["t", "v", "texture_v"]),
+                    ];
+                    let color = [find(&["red"]), find(&["green"]), find(&["blue"])];
+                    let [Some(x), Some(y), Some(z)] = position else {
+                        return Err(MeshError::Invalid("vertices need x, y and z".to_string()));
+                    };
+                    has_normals = normal.iter().all(Option::is_some);
+                    has_uvs = uv.iter().all(Option::is_some);
+                    has_colors = color.iter().all(Option::is_some);
+
+                    let mut values = Vec::with_capacity(element.properties.len());
+                    for _ in 0..element.count {
+                        values.clear();
+                        for property in &element.properties {
+                            values.push(property.read(&mut *reader)?);
+                        }
+                        let value = |i: Option<usize>| values[i.unwrap()][0];
+                        verts.push(Vec3::new(values[x][0], values[y][0], values[z][0]));
+                        if has_normals {
+                            normals.push(Vec3::from(normal.map(value)));
+                        }
+                        if has_uvs {
+                            // Like OBJ, v = 0 is the bottom of the image.
+                            uvs.push(Vec2::new(value(uv[0]), 1. - value(uv[1])));
+                        }
+                        if has_colors {
+                            colors.push([0, 1, 2].map(|c| {
+                                let property = &element.properties[color[c].unwrap()];
+                                to_color_channel(value(color[c]), property.kind)
+                            }));
+                        }
+                    }
+                }
+                "face" => {
+                    let list = element
+                        .properties
+                        .iter()
+                        .position(|p| matches!(p.name.as_str(), "vertex_indices" | "vertex_index"));
+                    let Some(list) = list else {
+                        return Err(MeshError::Invalid("faces need vertex_indices".to_string()));
+                    };
+                    for _ in 0..element.count {
+                        let mut face = Vec::new();
+                        for (i, property) in element.properties.iter().enumerate() {
+                            let values = property.read(&mut *reader)?;
+                            if i == list {
+                                face = values;
+                            }
+                        }
+                        let face: Vec<usize> = face.iter().map(|&i| i as usize).collect();
+                        if face.iter().any(|&i| i >= verts.len()) {
+                            return Err(MeshError::Invalid(format!(
+                                "a face refers to a vertex past the {} there are",
+                                verts.len()
+                            )));
+                        }
+                        for i in 1..face.len().saturating_sub(1) {
+                            triangles.push((face[0], face[i], face[i + 1]));
+                        }
+                    }
+                }
+                _ => {
+                    for _ in 0..element.count {
+                        for property in &element.properties {
+                            property.read(&mut *reader)?;
+                        }
+                    }
+                }
+            }
+        }
+        if verts.is_empty() {
+            return Err(MeshError::Invalid("the file has no vertices".to_string()));
+        }
+
+        let uvs = has_uvs.then_some(uvs);
+        // Point clouds keep whatever normals they come with, there are no faces to
+        // generate them from.
+        let generate_normals = !has_normals && !triangles.is_empty();
+        if !has_normals {
+            normals = vec![Vec3::zero(); verts.len()];
+        }
+        let mut mesh = Mesh::with_normals(verts, triangles, normals, uvs);
+        if has_colors {
+            mesh = mesh.with_colors(colors);
+        }
+        if generate_normals {
+            mesh.generate_normals(DEFAULT_CREASE_ANGLE);
+            mesh.generate_tangents();
+        }
+        Ok(mesh)
+    }
+
+    pub fn save_ply<P: AsRef<Path>>(&self, path: P, binary: bool) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_ply(&mut writer, binary)?;
+        writer.flush()
+    }
+
+    /// Writes positions, normals, texture coordinates, colors and triangles, ASCII
+    /// or little endian binary.
+    pub fn write_ply<W: Write>(&self, writer: &mut W, binary: bool) -> io::Result<()> {
+        let format = if binary {
+            "binary_little_endian"
+        } else {
+            "ascii"
+        };
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format {} 1.0", format)?;
+        writeln!(writer, "element vertex {}", self.verts.len())?;
+        for name in ["x", "y", "z", "nx", "ny", "nz"] {
+            writeln!(writer, "property float {}", name)?;
+        }
+        if self.uvs.is_some() {
+            writeln!(writer, "property float s")?;
+            writeln!(writer, "property float t")?;
+        }
+        if self.colors.is_some() {
+            for name in ["red", "green", "blue"] {
+                writeln!(writer, "property uchar {}", name)?;
+            }
+        }
+        writeln!(writer, "element face {}", self.triangles.len())?;
+        writeln!(writer, "property list uchar int vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        for i in 0..self.verts.len() {
+            let (v, n) = (self.verts[i], self.normals[i]);
+            let mut floats = vec![v.x, v.y, v.z, n.x, n.y, n.z];
+            if let Some(uvs) = &self.uvs {
+                floats.extend([uvs[i].x, 1. - uvs[i].y]);
+            }
+            let color = self.colors.as_ref().map(|colors| colors[i]);
+            if binary {
+                for f in floats {
+                    writer.write_all(&(f as f32).to_le_bytes())?;
+                }
+                if let Some(color) = color {
+                    writer.write_all(&color)?;
+                }
+            } else {
+                let mut words: Vec<String> = floats.iter().map(|f| f.to_string()).collect();
+                if let Some(color) = color {
+                    words.extend(color.iter().map(|c| c.to_string()));
+                }
+                writeln!(writer, "{}", words.join(" "))?;
+            }
+        }
+        for tri in &self.triangles {
+            if binary {
+                writer.write_all(&[3])?;
+                for v in [tri.0, tri.1, tri.2] {
+                    writer.write_all(&(v as i32).to_le_bytes())?;
+                }
+            } else {
+                writeln!(writer, "3 {} {} {}", tri.0, tri.1, tri.2)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar, MeshError> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(MeshError::Invalid(format!("unknown type '{}'", name))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+impl Property {
+    /// One value for scalars, all of them for lists.
+    fn read(&self, reader: &mut dyn ValueReader) -> Result<Vec<f64>, MeshError> {
+        match self.kind {
+            PropertyKind::Scalar(scalar) => Ok(vec![reader.read(scalar)?]),
+            PropertyKind::List { count, item } => {
+                let count = reader.read(count)? as usize;
+                (0..count).map(|_| reader.read(item)).collect()
+            }
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+/// Splits the file into its parsed header and the data after it.
+fn parse_header(bytes: &[u8]) -> Result<(Header, &[u8]), MeshError> {
+    let end = b"end_header";
+    let end_at = bytes
+        .windows(end.len())
+        .position(|w| w == end)
+        .ok_or_else(|| MeshError::Invalid("no end_header".to_string()))?;
+    let mut body_at = end_at + end.len();
+    while body_at < bytes.len() && bytes[body_at] != b'\n' {
+        body_at += 1;
+    }
+    let text = std::str::from_utf8(&bytes[..end_at])
+        .map_err(|_| MeshError::Invalid("the header isn't text".to_string()))?;
+
+    let mut lines = text.lines().enumerate();
+    if lines.next().map(|(_, l)| l.trim()) != Some("ply") {
+        return Err(MeshError::Invalid("not a PLY file".to_string()));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for (line_index, line) in lines {
+        let parse_error = |msg: &str| MeshError::Parse {
+            line: line_index + 1,
+            msg: msg.to_string(),
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(Format::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| parse_error("the element count isn't a number"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| parse_error("property outside of an element"))?;
+                if element.name == "vertex" && VERTEX_SCALARS.contains(name) {
+                    return Err(parse_error(&format!("vertex property {} is a list", name)));
+                }
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List {
+                        count: Scalar::parse(count)?,
+                        item: Scalar::parse(item)?,
+                    },
+                });
+            }
+            ["property", scalar, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| parse_error("property outside of an element"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(Scalar::parse(scalar)?),
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => (),
+            _ => return Err(parse_error("unknown header line")),
+        }
+    }
+    let format = format.ok_or_else(|| MeshError::Invalid("no format line".to_string()))?;
+    let body = bytes.get(body_at + 1..).unwrap_or(&[]);
+    Ok((Header { format, elements }, body))
+}
+
+trait ValueReader {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, MeshError>;
+}
+
+struct AsciiReader<'a> {
+    words: std::str::SplitWhitespace<'a>,
+}
+impl ValueReader for AsciiReader<'_> {
+    fn read(&mut self, _: Scalar) -> Result<f64, MeshError> {
+        let word = self
+            .words
+            .next()
+            .ok_or_else(|| MeshError::Invalid("the file ends early".to_string()))?;
+        word.parse()
+            .map_err(|_| MeshError::Invalid(format!("'{}' is not a number", word)))
+    }
+}
+
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+impl ValueReader for BinaryReader<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, MeshError> {
+        let size = scalar.size();
+        if self.bytes.len() < size {
+            return Err(MeshError::Invalid("the file ends early".to_string()));
+        }
+        let mut raw = [0u8; 8];
+        raw[..size].copy_from_slice(&self.bytes[..size]);
+        self.bytes = &self.bytes[size..];
+        if self.big_endian {
+            raw[..size].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = raw;
+        Ok(match scalar {
+            Scalar::I8 => b0 as i8 as f64,
+            Scalar::U8 => b0 as f64,
+            Scalar::I16 => i16::from_le_bytes([b0, b1]) as f64,
+            Scalar::U16 => u16::from_le_bytes([b0, b1]) as f64,
+            Scalar::I32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::U32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::F32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            Scalar::F64 => f64::from_le_bytes(raw),
+        })
+    }
+}
+
+/// Float colors run from 0 to 1, integer ones from 0 to 255.
+fn to_color_channel(value: f64, kind: PropertyKind) -> u8 {
+    match kind {
+        PropertyKind::Scalar(Scalar::F32 | Scalar::F64) => {
+            (value.clamp(0., 1.) * 255.).round() as u8
+        }
+        _ => value.clamp(0., 255.) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn assert_close(a: &[Vec3], b: &[Vec3]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).magnitude() < 1e-6, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn round_trip(mesh: &Mesh, binary: bool) -> Mesh {
+        let mut bytes = Vec::new();
+        mesh.write_ply(&mut bytes, binary).unwrap();
+        let loaded = Mesh::parse_ply(&bytes).unwrap();
+        assert_close(&loaded.verts, &mesh.verts);
+        assert_close(&loaded.normals, &mesh.normals);
+        assert_eq!(loaded.triangles, mesh.triangles);
+        assert_eq!(loaded.colors, mesh.colors);
+        loaded
+    }
+
+    fn colored_cube() -> Mesh {
+        let cube = primitives::cube();
+        let colors = (0..cube.verts.len())
+            .map(|i| [i as u8 * 10, 255 - i as u8, 7])
+            .collect();
+        cube.with_colors(colors)
+    }
+
+    fn point_cloud() -> Mesh {
+        let verts: Vertices = (0..5).map(|i| Vec3::new(i as f64, 0.5, -2.)).collect();
+        let normals = vec![Vec3::unit_y(); verts.len()];
+        let colors = vec![[200, 100, 0]; verts.len()];
+        Mesh::with_normals(verts, Vec::new(), normals, None).with_colors(colors)
+    }
+
+    #[test]
+    fn colored_round_trip() {
+        for binary in [false, true] {
+            round_trip(&colored_cube(), binary);
+        }
+    }
+
+    #[test]
+    fn point_cloud_round_trip() {
+        for binary in [false, true] {
+            let loaded = round_trip(&point_cloud(), binary);
+            assert!(loaded.triangles.is_empty());
+        }
+    }
+
+    #[test]
+    fn truncated_binary_body() {
+        for mesh in [colored_cube(), point_cloud()] {
+            let mut bytes = Vec::new();
+            mesh.write_ply(&mut bytes, true).unwrap();
+            bytes.truncate(bytes.len() - 5);
+            let error = Mesh::parse_ply(&bytes).unwrap_err();
+            assert_eq!(error.to_string(), "the file ends early");
+        }
+    }
+}
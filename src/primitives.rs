@@ -0,0 +1,290 @@
+use crate::{consts::*, mesh::Mesh};
+use cgmath::*;
+use std::{collections::HashMap, f64::consts::PI};
+
+pub fn cube() -> Mesh {
+    let verts = vec![
+        Vec3::new(1., 1., 1.),
+        Vec3::new(-1., 1., 1.),
+        Vec3::new(-1., -1., 1.),
+        Vec3::new(1., -1., 1.),
+        Vec3::new(1., 1., -1.),
+        Vec3::new(-1., 1., -1.),
+        Vec3::new(-1., -1., -1.),
+        Vec3::new(1., -1., -1.),
+    ];
+    let triangles = vec![
+        (0, 1, 2),
+        (0, 2, 3),
+        (4, 0, 3),
+        (4, 3, 7),
+        (5, 4, 7),
+        (5, 7, 6),
+        (1, 5, 6),
+        (1, 6, 2),
+        (4, 5, 1),
+        (4, 1, 0),
+        (2, 6, 7),
+        (2, 7, 3),
+    ];
+    Mesh::new(verts, triangles, None)
+}
+
+pub fn uv_sphere(segments: usize, rings: usize) -> Mesh {
+    let rings = rings.max(2);
+    let profile: Vec<ProfilePoint> = (0..=rings)
+        .map(|i| {
+            let phi = PI * i as f64 / rings as f64;
+            ProfilePoint::new(phi.sin(), phi.cos(), Vec2::new(phi.sin(), phi.cos()))
+        })
+        .collect();
+    lathe(&profile, segments)
+}
+
+pub fn icosphere(subdivisions: usize) -> Mesh {
+    let t = (1. + 5f64.sqrt()) / 2.;
+    let mut verts: Vertices = [
+        (-1., t, 0.),
+        (1., t, 0.),
+        (-1., -t, 0.),
+        (1., -t, 0.),
+        (0., -1., t),
+        (0., 1., t),
+        (0., -1., -t),
+        (0., 1., -t),
+        (t, 0., -1.),
+        (t, 0., 1.),
+        (-t, 0., -1.),
+        (-t, 0., 1.),
+    ]
+    .iter()
+    .map(|&(x, y, z)| Vec3::new(x, y, z).normalize())
+    .collect();
+    let mut triangles = vec![
+        (0, 11, 5),
+        (0, 5, 1),
+        (0, 1, 7),
+        (0, 7, 10),
+        (0, 10, 11),
+        (1, 5, 9),
+        (5, 11, 4),
+        (11, 10, 2),
+        (10, 7, 6),
+        (7, 1, 8),
+        (3, 9, 4),
+        (3, 4, 2),
+        (3, 2, 6),
+        (3, 6, 8),
+        (3, 8, 9),
+        (4, 9, 5),
+        (2, 4, 11),
+        (6, 2, 10),
+        (8, 6, 7),
+        (9, 8, 1),
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize, verts: &mut Vertices| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                verts.push(((verts[a] + verts[b]) / 2.).normalize());
+                verts.len() - 1
+            })
+        };
+        let mut subdivided = Vec::with_capacity(triangles.len() * 4);
+        for &(a, b, c) in &triangles {
+            let ab = midpoint(a, b, &mut verts);
+            let bc = midpoint(b, c, &mut verts);
+            let ca = midpoint(c, a, &mut verts);
+            subdivided.extend([(a, ab, ca), (b, bc, ab), (c, ca, bc), (ab, bc, ca)]);
+        }
+        triangles = subdivided;
+    }
+
+    // Wind every face so its cross product points away from the center.
+    for tri in &mut triangles {
+        let (v0, v1, v2) = (verts[tri.0], verts[tri.1], verts[tri.2]);
+        if (v1 - v0).cross(v2 - v0).dot(v0 + v1 + v2) < 0. {
+            std::mem::swap(&mut tri.1, &mut tri.2);
+        }
+    }
+
+    let uvs = verts
+        .iter()
+        .map(|v| Vec2::new(0.5 + v.z.atan2(v.x) / (2. * PI), v.y.acos() / PI))
+        .collect();
+    let normals = verts.clone();
+    Mesh::with_normals(verts, triangles, normals, Some(uvs))
+}
+
+pub fn cylinder(segments: usize) -> Mesh {
+    let profile = [
+        ProfilePoint::new(1., 1., Vec2::new(1., 0.)),
+        ProfilePoint::new(1., -1., Vec2::new(1., 0.)),
+    ];
+    let mut mesh = lathe(&profile, segments);
+    add_cap(&mut mesh, 1., 1., segments);
+    add_cap(&mut mesh, -1., 1., segments);
+    mesh.generate_tangents();
+    mesh
+}
+
+pub fn cone(segments: usize) -> Mesh {
+    // The side rises 2 units over a radius of 1, so its normal leans up by 1 in 2.
+    let normal = Vec2::new(2., 1.).normalize();
+    let profile = [
+        ProfilePoint::new(0., 1., normal),
+        ProfilePoint::new(1., -1., normal),
+    ];
+    let mut mesh = lathe(&profile, segments);
+    add_cap(&mut mesh, -1., 1., segments);
+    mesh.generate_tangents();
+    mesh
+}
+
+/// A ring around the y axis with an outer radius of 1.
+pub fn torus(segments: usize, sides: usize, tube_radius: f64) -> Mesh {
+    let sides = sides.max(3);
+    let major_radius = 1. - tube_radius;
+    let profile: Vec<ProfilePoint> = (0..=sides)
+        .map(|i| {
+            // Walk the tube cross-section downwards on the outside first, like a sphere.
+            let phi = -2. * PI * i as f64 / sides as f64;
+            let normal = Vec2::new(phi.cos(), phi.sin());
+            ProfilePoint::new(
+                major_radius + tube_radius * normal.x,
+                tube_radius * normal.y,
+                normal,
+            )
+        })
+        .collect();
+    lathe(&profile, segments)
+}
+
+/// A flat grid in the xz plane facing +y, split into `subdivisions` squares per side.
+pub fn plane(subdivisions: usize) -> Mesh {
+    let n = subdivisions.max(1);
+    let mut verts = Vec::new();
+    let mut uvs = Vec::new();
+    for i in 0..=n {
+        for j in 0..=n {
+            let (u, v) = (j as f64 / n as f64, i as f64 / n as f64);
+            verts.push(Vec3::new(-1. + 2. * u, 0., 1. - 2. * v));
+            uvs.push(Vec2::new(u, v));
+        }
+    }
+    let normals = vec![Vec3::unit_y(); verts.len()];
+    let triangles = grid_triangles(&verts, n, n);
+    Mesh::with_normals(verts, triangles, normals, Some(uvs))
+}
+
+/// A cylinder of the given `height` with a half sphere of radius 1 on both ends.
+pub fn capsule(segments: usize, rings: usize, height: f64) -> Mesh {
+    let half_rings = (rings / 2).max(1);
+    let half_height = height / 2.;
+    let mut profile = Vec::new();
+    for i in 0..=half_rings {
+        let phi = PI / 2. * i as f64 / half_rings as f64;
+        let normal = Vec2::new(phi.sin(), phi.cos());
+        profile.push(ProfilePoint::new(normal.x, normal.y + half_height, normal));
+    }
+    for i in 0..=half_rings {
+        let phi = PI / 2. + PI / 2. * i as f64 / half_rings as f64;
+        let normal = Vec2::new(phi.sin(), phi.cos());
+        profile.push(ProfilePoint::new(normal.x, normal.y - half_height, normal));
+    }
+    lathe(&profile, segments)
+}
+
+/// A point of a profile curve in the xy plane, given as distance from the y axis.
+struct ProfilePoint {
+    radius: f64,
+    y: f64,
+    /// Normal in (radial, y) components.
+    normal: Vec2,
+}
+impl ProfilePoint {
+    fn new(radius: f64, y: f64, normal: Vec2) -> Self {
+        Self { radius, y, normal }
+    }
+}
+
+/// Sweeps a profile, listed from top to bottom, around the y axis. The seam gets
+/// duplicated vertices so u can run from 0 to 1, v runs along the profile.
+fn lathe(profile: &[ProfilePoint], segments: usize) -> Mesh {
+    let segments = segments.max(3);
+    let mut verts = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+
+    for (i, point) in profile.iter().enumerate() {
+        for j in 0..=segments {
+            let theta = 2. * PI * j as f64 / segments as f64;
+            let (sin, cos) = theta.sin_cos();
+            verts.push(Vec3::new(point.radius * cos, point.y, point.radius * sin));
+            normals.push(Vec3::new(
+                point.normal.x * cos,
+                point.normal.y,
+                point.normal.x * sin,
+            ));
+            uvs.push(Vec2::new(
+                j as f64 / segments as f64,
+                i as f64 / (profile.len() - 1) as f64,
+            ));
+        }
+    }
+
+    let triangles = grid_triangles(&verts, segments, profile.len() - 1);
+    Mesh::with_normals(verts, triangles, normals, Some(uvs))
+}
+
+/// Triangulates a (columns + 1) x (rows + 1) grid of vertices stored row by row,
+/// leaving out the zero-area triangles found at poles and tips.
+fn grid_triangles(verts: &Vertices, columns: usize, rows: usize) -> Vec<Indices> {
+    let mut triangles = Vec::new();
+    for i in 0..rows {
+        for j in 0..columns {
+            let a = i * (columns + 1) + j;
+            let (b, c) = (a + 1, a + columns + 1);
+            let d = c + 1;
+            for tri in [(a, b, c), (b, d, c)] {
+                let (v0, v1, v2) = (verts[tri.0], verts[tri.1], verts[tri.2]);
+                if (v1 - v0).cross(v2 - v0).magnitude2() > 1e-12 {
+                    triangles.push(tri);
+                }
+            }
+        }
+    }
+    triangles
+}
+
+/// Closes the end of a lathe at height `y` with a flat disc facing away from the center.
+fn add_cap(mesh: &mut Mesh, y: f64, radius: f64, segments: usize) {
+    let segments = segments.max(3);
+    let normal = Vec3::new(0., y.signum(), 0.);
+    let center = mesh.verts.len();
+    mesh.verts.push(Vec3::new(0., y, 0.));
+    mesh.normals.push(normal);
+    if let Some(uvs) = &mut mesh.uvs {
+        uvs.push(Vec2::new(0.5, 0.5));
+    }
+
+    for j in 0..=segments {
+        let theta = 2. * PI * j as f64 / segments as f64;
+        let (sin, cos) = theta.sin_cos();
+        mesh.verts.push(Vec3::new(radius * cos, y, radius * sin));
+        mesh.normals.push(normal);
+        if let Some(uvs) = &mut mesh.uvs {
+            uvs.push(Vec2::new(0.5 + 0.5 * cos, 0.5 + 0.5 * sin));
+        }
+    }
+
+    for j in 0..segments {
+        let (p0, p1) = (center + 1 + j, center + 2 + j);
+        if y > 0. {
+            mesh.triangles.push((center, p1, p0));
+        } else {
+            mesh.triangles.push((center, p0, p1));
+        }
+    }
+}
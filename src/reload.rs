@@ -0,0 +1,94 @@
+use crate::{consts::*, mesh::Mesh, scene::Scene};
+use log::{error, info};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Instant, SystemTime},
+};
+
+/// Polls the modification times of a scene file and the mesh files it uses, and
+/// rebuilds the affected parts of the scene when one of them changes. Polling
+/// instead of OS notifications keeps it working on every platform and file system.
+pub struct SceneWatcher {
+    scene_path: PathBuf,
+    scene_modified: Option<SystemTime>,
+    mesh_modified: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+impl SceneWatcher {
+    pub fn new<P: Into<PathBuf>>(scene_path: P, scene: &Scene) -> Self {
+        let scene_path = scene_path.into();
+        Self {
+            scene_modified: modified(&scene_path),
+            mesh_modified: watch_meshes(scene),
+            scene_path,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Reloads whatever changed since the last poll. The camera is left alone, and
+    /// a file that fails to load is reported and keeps its previous contents.
+    pub fn poll(&mut self, scene: &mut Scene) {
+        if self.last_poll.elapsed().as_secs_f64() < RELOAD_POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        let scene_modified = modified(&self.scene_path);
+        if scene_modified != self.scene_modified {
+            self.scene_modified = scene_modified;
+            match Scene::load(&self.scene_path) {
+                Ok(new_scene) => {
+                    info!("reloaded {}", self.scene_path.display());
+                    scene.instances = new_scene.instances;
+                    scene.graph = new_scene.graph;
+                    scene.animations = new_scene.animations;
+                    scene.clock = new_scene.clock;
+                    scene.lights = new_scene.lights;
+                    scene.background = new_scene.background;
+                    scene.fog = new_scene.fog;
+                    scene.depth_cue = new_scene.depth_cue;
+                    scene.mesh_files = new_scene.mesh_files;
+                    // The scene just loaded every mesh again, so they're all up to date.
+                    self.mesh_modified = watch_meshes(scene);
+                }
+                Err(e) => error!("failed to reload {}: {}", self.scene_path.display(), e),
+            }
+            return;
+        }
+
+        for (path, last_modified) in &mut self.mesh_modified {
+            let now_modified = modified(path);
+            if now_modified == *last_modified {
+                continue;
+            }
+            *last_modified = now_modified;
+
+            let (format, instances) = &scene.mesh_files[&*path];
+            match Mesh::load_as(&*path, *format) {
+                Ok(mesh) => {
+                    info!("reloaded {}", path.display());
+                    let mesh = Rc::new(mesh);
+                    for &i in instances {
+                        scene.instances[i].set_mesh(mesh.clone());
+                    }
+                }
+                Err(e) => error!("failed to reload {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+fn watch_meshes(scene: &Scene) -> Vec<(PathBuf, Option<SystemTime>)> {
+    scene
+        .mesh_files
+        .keys()
+        .map(|path| (path.clone(), modified(path)))
+        .collect()
+}
+
+/// `None` while the file is missing, e.g. halfway through an editor saving it.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,819 @@
+use crate::{
+    animation::{self, Animation, Clock, Interpolation, Track},
+    consts::*,
+    environment::{Background, Cubemap},
+    fog::{Fog, FogFalloff},
+    gltf_import::load_gltf,
+    instance::{Instance, Model, Transform},
+    light::Light,
+    material::{Material, RenderMode, Texture},
+    mesh::{Mesh, MeshError, MeshFormat},
+    scene_graph::SceneGraph,
+    shader,
+    skeleton::{Clip, Skeleton, Skin},
+};
+use ron::extensions::Extensions;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+pub struct Scene {
+    /// Everything that gets drawn. Their transforms are set from `graph`.
+    pub instances: Vec<Instance>,
+    pub graph: SceneGraph,
+    pub animations: Vec<Animation>,
+    pub clock: Clock,
+    pub lights: Vec<Light>,
+    pub camera: Transform,
+    pub background: Background,
+    pub fog: Option<Fog>,
+    /// Fades wireframe lines by depth. Without it lines take the regular fog.
+    pub depth_cue: Option<Fog>,
+    /// Mesh files the scene was built from, with the format they were read as and the
+    /// indices of the instances using them.
+    pub mesh_files: HashMap<PathBuf, (MeshFormat, Vec<usize>)>,
+}
+impl Scene {
+    /// Loads a RON scene file, or a glTF file with `load_gltf` if it ends in .gltf or
+    /// .glb. Mesh paths inside a RON file are relative to the file itself.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if extension.eq_ignore_ascii_case("gltf") || extension.eq_ignore_ascii_case("glb") {
+            return load_gltf(path);
+        }
+        let source = fs::read_to_string(path).map_err(|e| SceneError::Io(path.into(), e))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Scene::parse(&source, base_dir)
+    }
+
+    pub fn parse(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        // Lets instances write `mesh: "cube"` instead of `mesh: Some("cube")`.
+        let options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+        let desc: SceneDesc = options.from_str(source).map_err(SceneError::Parse)?;
+
+        let mut meshes = HashMap::new();
+        let mut mesh_paths = HashMap::new();
+        for (name, mesh_desc) in &desc.meshes {
+            let mesh = match mesh_desc {
+                MeshDesc::Obj(path) | MeshDesc::Stl(path) | MeshDesc::Ply(path) => {
+                    let path = base_dir.join(path);
+                    let format = match mesh_desc {
+                        MeshDesc::Stl(_) => MeshFormat::Stl,
+                        MeshDesc::Ply(_) => MeshFormat::Ply,
+                        _ => MeshFormat::Obj,
+                    };
+                    let mesh = Mesh::load_as(&path, format).map_err(|error| SceneError::Mesh {
+                        name: name.clone(),
+                        path: path.clone(),
+                        error,
+                    })?;
+                    mesh_paths.insert(name.as_str(), (path, format));
+                    mesh
+                }
+                MeshDesc::Primitive(model) => model.get_mesh(),
+            };
+            meshes.insert(name.as_str(), Rc::new(mesh));
+        }
+
+        let mut builder = GraphBuilder {
+            source,
+            base_dir,
+            meshes,
+            mesh_paths,
+            textures: HashMap::new(),
+            instances: Vec::new(),
+            graph: SceneGraph::default(),
+            mesh_files: HashMap::new(),
+        };
+        for inst_desc in &desc.instances {
+            builder.add(inst_desc, None)?;
+        }
+        let GraphBuilder {
+            mut instances,
+            graph,
+            mesh_files,
+            ..
+        } = builder;
+        let animations = desc
+            .animations
+            .iter()
+            .map(|anim| anim.build(&graph, source))
+            .collect::<Result<_, _>>()?;
+        graph.update(&mut instances);
+
+        let lights = desc.lights.iter().map(LightDesc::build).collect();
+        let background = desc.background.build(base_dir)?;
+        let mut camera = Transform::new(to_vec3(desc.camera.translation), 1.);
+        camera.rot = desc.camera.rotation;
+        for fog in desc.fog.iter().chain(&desc.depth_cue) {
+            check_fog(fog)?;
+        }
+
+        Ok(Scene {
+            instances,
+            graph,
+            animations,
+            clock: desc.clock,
+            lights,
+            camera,
+            background,
+            fog: desc.fog,
+            depth_cue: desc.depth_cue,
+            mesh_files,
+        })
+    }
+}
+impl Scene {
+    /// Length of the longest animation or skin clip, which is where the clock loops.
+    pub fn animation_duration(&self) -> f64 {
+        self.instances
+            .iter()
+            .filter_map(|instance| instance.skin.as_ref())
+            .map(Skin::duration)
+            .fold(animation::duration(&self.animations), f64::max)
+    }
+}
+impl Default for Scene {
+    fn default() -> Self {
+        let instances = vec![
+            Instance::new(
+                Model::Cube,
+                Vec3::new(0., 0., 0.),
+                1.,
+                Material::new(GREEN, RenderMode::Phong).with_specular(50.),
+            ),
+            //Instance::new(Model::Cube, Vec3::new(2.5, 0., 0.), 1., Material::default()),
+        ];
+        let graph = SceneGraph::from_instances(&instances);
+        let mut instances = instances;
+        graph.update(&mut instances);
+        Scene {
+            instances,
+            graph,
+            animations: Vec::new(),
+            clock: Clock::default(),
+            lights: vec![
+                Light::Ambient { intensity: 0.2 },
+                Light::Point {
+                    intensity: 0.6,
+                    position: Vec3::new(2., 1., -4.),
+                },
+                Light::Directional {
+                    intensity: 0.2,
+                    direction: Vec3::new(-1., -4., 4.),
+                    shadows: false,
+                },
+            ],
+            camera: Transform::new(Vec3::new(0., 0., 0.), 1.),
+            background: Background::default(),
+            fog: None,
+            depth_cue: None,
+            mesh_files: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Scene", deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default)]
+    meshes: HashMap<String, MeshDesc>,
+    #[serde(default)]
+    instances: Vec<InstanceDesc>,
+    #[serde(default)]
+    animations: Vec<AnimationDesc>,
+    #[serde(default)]
+    clock: Clock,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+    #[serde(default)]
+    camera: CameraDesc,
+    #[serde(default)]
+    background: BackgroundDesc,
+    #[serde(default)]
+    fog: Option<Fog>,
+    #[serde(default)]
+    depth_cue: Option<Fog>,
+}
+
+#[derive(Deserialize)]
+enum MeshDesc {
+    Obj(PathBuf),
+    Stl(PathBuf),
+    Ply(PathBuf),
+    Primitive(Model),
+}
+
+/// A scene graph node, drawing `mesh` if it has one. `children` are placed relative
+/// to it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstanceDesc {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    mesh: Option<String>,
+    #[serde(default)]
+    translation: (f64, f64, f64),
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default = "one")]
+    scale: f64,
+    #[serde(default)]
+    material: MaterialDesc,
+    #[serde(default)]
+    sub_materials: Vec<MaterialRangeDesc>,
+    #[serde(default)]
+    skin: Option<SkinDesc>,
+    #[serde(default)]
+    children: Vec<InstanceDesc>,
+}
+
+/// State shared while walking the nested `InstanceDesc`s.
+struct GraphBuilder<'a> {
+    source: &'a str,
+    base_dir: &'a Path,
+    meshes: HashMap<&'a str, Rc<Mesh>>,
+    mesh_paths: HashMap<&'a str, (PathBuf, MeshFormat)>,
+    /// Textures built so far, so instances sharing one don't each decode and keep a copy.
+    textures: HashMap<&'a TextureDesc, Rc<Texture>>,
+    instances: Vec<Instance>,
+    graph: SceneGraph,
+    mesh_files: HashMap<PathBuf, (MeshFormat, Vec<usize>)>,
+}
+impl<'a> GraphBuilder<'a> {
+    fn add(&mut self, desc: &'a InstanceDesc, parent: Option<usize>) -> Result<(), SceneError> {
+        let mut local = Transform::new(to_vec3(desc.translation), desc.scale);
+        local.rot = desc.rotation;
+
+        let instance = match &desc.mesh {
+            Some(mesh_name) => {
+                let mesh =
+                    self.meshes
+                        .get(mesh_name.as_str())
+                        .ok_or_else(|| SceneError::UnknownMesh {
+                            name: mesh_name.clone(),
+                            line: line_of(self.source, &format!("\"{}\"", mesh_name)),
+                        })?;
+                if let Some((path, format)) = self.mesh_paths.get(mesh_name.as_str()) {
+                    self.mesh_files
+                        .entry(path.clone())
+                        .or_insert_with(|| (*format, Vec::new()))
+                        .1
+                        .push(self.instances.len());
+                }
+                let mut instance = Instance::from_mesh(
+                    mesh.clone(),
+                    local.translation,
+                    local.scale,
+                    desc.material.build(self.base_dir, &mut self.textures)?,
+                );
+                for range in &desc.sub_materials {
+                    instance.set_material_range(
+                        range.start..range.end,
+                        range.material.build(self.base_dir, &mut self.textures)?,
+                    );
+                }
+                if let Some(skin) = &desc.skin {
+                    instance.set_skin(skin.build(self.source)?);
+                }
+                self.instances.push(instance);
+                Some(self.instances.len() - 1)
+            }
+            None => None,
+        };
+
+        let node = self.graph.add_node(parent, local, instance);
+        self.graph.nodes[node].name = desc.name.clone();
+        for child in &desc.children {
+            self.add(child, Some(node))?;
+        }
+        Ok(())
+    }
+}
+
+/// Keyframes for the node called `target`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnimationDesc {
+    target: String,
+    #[serde(default)]
+    translation: TrackDesc<(f64, f64, f64)>,
+    #[serde(default)]
+    rotation: TrackDesc<f64>,
+    #[serde(default)]
+    scale: TrackDesc<f64>,
+}
+impl AnimationDesc {
+    fn build(&self, graph: &SceneGraph, source: &str) -> Result<Animation, SceneError> {
+        let node = graph
+            .find(&self.target)
+            .ok_or_else(|| SceneError::UnknownNode {
+                name: self.target.clone(),
+                line: line_of(source, &format!("\"{}\"", self.target)),
+            })?;
+        Ok(build_animation(
+            node,
+            &self.translation,
+            &self.rotation,
+            &self.scale,
+        ))
+    }
+}
+
+fn build_animation(
+    node: usize,
+    translation: &TrackDesc<(f64, f64, f64)>,
+    rotation: &TrackDesc<f64>,
+    scale: &TrackDesc<f64>,
+) -> Animation {
+    let translation_keys = translation.keys.iter();
+    Animation {
+        node,
+        translation: Track::new(
+            translation.interpolation,
+            translation_keys
+                .map(|&(time, v)| (time, to_vec3(v)))
+                .collect(),
+        ),
+        rotation: Track::new(rotation.interpolation, rotation.keys.clone()),
+        scale: Track::new(scale.interpolation, scale.keys.clone()),
+    }
+}
+
+/// `keys` are `(time, value)` pairs, time in seconds.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct TrackDesc<T> {
+    #[serde(default)]
+    interpolation: Interpolation,
+    keys: Vec<(f64, T)>,
+}
+
+/// Joints are listed parents first, placed in the mesh's space in the pose it was
+/// modelled in. Meshes without joint weights get weighted by distance to the bones.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SkinDesc {
+    joints: Vec<JointDesc>,
+    #[serde(default)]
+    clips: Vec<ClipDesc>,
+}
+impl SkinDesc {
+    fn build(&self, source: &str) -> Result<Skin, SceneError> {
+        if self.joints.is_empty() {
+            return Err(SceneError::Invalid(
+                "a skin needs at least one joint".to_string(),
+            ));
+        }
+        let mut skeleton = Skeleton::default();
+        for joint in &self.joints {
+            let parent = match &joint.parent {
+                Some(parent) => Some(find_joint(&skeleton, parent, source)?),
+                None => None,
+            };
+            let mut rest = Transform::new(to_vec3(joint.translation), joint.scale);
+            rest.rot = joint.rotation;
+            skeleton.add_joint(&joint.name, parent, rest);
+        }
+
+        let mut clips = Vec::with_capacity(self.clips.len());
+        for clip in &self.clips {
+            let mut channels = Vec::with_capacity(clip.channels.len());
+            for channel in &clip.channels {
+                channels.push(build_animation(
+                    find_joint(&skeleton, &channel.joint, source)?,
+                    &channel.translation,
+                    &channel.rotation,
+                    &channel.scale,
+                ));
+            }
+            clips.push(Clip {
+                name: clip.name.clone(),
+                channels,
+            });
+        }
+        Ok(Skin::new(Rc::new(skeleton), clips))
+    }
+}
+
+fn find_joint(skeleton: &Skeleton, name: &str, source: &str) -> Result<usize, SceneError> {
+    skeleton.find(name).ok_or_else(|| SceneError::UnknownJoint {
+        name: name.to_string(),
+        line: line_of(source, &format!("\"{}\"", name)),
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JointDesc {
+    name: String,
+    #[serde(default)]
+    parent: Option<String>,
+    #[serde(default)]
+    translation: (f64, f64, f64),
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default = "one")]
+    scale: f64,
+}
+
+/// The first clip plays on the scene's clock.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClipDesc {
+    name: String,
+    channels: Vec<ChannelDesc>,
+}
+
+/// Keyframes for the joint called `joint`, like `AnimationDesc` does for instances.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChannelDesc {
+    joint: String,
+    #[serde(default)]
+    translation: TrackDesc<(f64, f64, f64)>,
+    #[serde(default)]
+    rotation: TrackDesc<f64>,
+    #[serde(default)]
+    scale: TrackDesc<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialRangeDesc {
+    start: usize,
+    end: usize,
+    material: MaterialDesc,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDesc {
+    #[serde(default = "white")]
+    color: [u8; 3],
+    #[serde(default = "wireframe")]
+    mode: RenderMode,
+    #[serde(default = "matte")]
+    specular: f64,
+    #[serde(default)]
+    reflectivity: f64,
+    #[serde(default)]
+    texture: Option<TextureDesc>,
+    #[serde(default)]
+    normal_map: Option<TextureDesc>,
+    #[serde(default = "point_size")]
+    point_size: f64,
+    #[serde(default)]
+    point_distance: Option<f64>,
+    #[serde(default)]
+    shader: Option<ShaderDesc>,
+}
+impl MaterialDesc {
+    fn build<'a>(
+        &'a self,
+        base_dir: &Path,
+        textures: &mut HashMap<&'a TextureDesc, Rc<Texture>>,
+    ) -> Result<Material, SceneError> {
+        let positive = |value: f64| value.is_finite() && value > 0.;
+        if !positive(self.point_size) || !self.point_distance.is_none_or(positive) {
+            return Err(SceneError::Invalid(
+                "point_size and point_distance must be positive".to_string(),
+            ));
+        }
+        let mut material = Material::new(self.color, self.mode)
+            .with_specular(self.specular)
+            .with_reflectivity(self.reflectivity)
+            .with_point_size(self.point_size, self.point_distance);
+        if let Some(texture) = &self.texture {
+            material = material.with_texture(texture.build_cached(base_dir, textures)?);
+        }
+        if let Some(normal_map) = &self.normal_map {
+            material = material.with_normal_map(normal_map.build_cached(base_dir, textures)?);
+        }
+        if let Some(ShaderDesc::Toon { bands }) = self.shader {
+            material = material.with_shader(shader::toon_shader(bands));
+        }
+        Ok(material)
+    }
+}
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        Self {
+            color: white(),
+            mode: wireframe(),
+            specular: matte(),
+            reflectivity: 0.,
+            texture: None,
+            normal_map: None,
+            point_size: point_size(),
+            point_distance: None,
+            shader: None,
+        }
+    }
+}
+
+/// Shaders that need no data besides the scene. Others are attached in code with
+/// `Material::with_shader`.
+#[derive(Deserialize)]
+enum ShaderDesc {
+    Toon { bands: usize },
+}
+
+#[derive(PartialEq, Eq, Hash, Deserialize)]
+enum TextureDesc {
+    File(PathBuf),
+    Checkerboard {
+        size: usize,
+        squares: usize,
+        colors: ([u8; 3], [u8; 3]),
+    },
+}
+impl TextureDesc {
+    fn build_cached<'a>(
+        &'a self,
+        base_dir: &Path,
+        textures: &mut HashMap<&'a TextureDesc, Rc<Texture>>,
+    ) -> Result<Rc<Texture>, SceneError> {
+        if let Some(texture) = textures.get(self) {
+            return Ok(texture.clone());
+        }
+        let texture = self.build(base_dir)?;
+        textures.insert(self, texture.clone());
+        Ok(texture)
+    }
+
+    fn build(&self, base_dir: &Path) -> Result<Rc<Texture>, SceneError> {
+        let texture = match self {
+            TextureDesc::File(path) => {
+                let path = base_dir.join(path);
+                Texture::load(&path).map_err(|error| SceneError::Texture {
+                    path: path.clone(),
+                    error,
+                })?
+            }
+            TextureDesc::Checkerboard {
+                size,
+                squares,
+                colors,
+            } => {
+                if *size == 0 || *squares == 0 {
+                    return Err(SceneError::Invalid(
+                        "checkerboard size and squares must be at least 1".to_string(),
+                    ));
+                }
+                Texture::checkerboard(*size, *squares, colors.0, colors.1)
+            }
+        };
+        Ok(Rc::new(texture))
+    }
+}
+
+#[derive(Deserialize)]
+enum BackgroundDesc {
+    Solid([u8; 3]),
+    Gradient {
+        top: [u8; 3],
+        bottom: [u8; 3],
+    },
+    /// Cubemap face images, see `Cubemap` for how they're oriented.
+    Skybox {
+        right: PathBuf,
+        left: PathBuf,
+        top: PathBuf,
+        bottom: PathBuf,
+        front: PathBuf,
+        back: PathBuf,
+    },
+}
+impl BackgroundDesc {
+    fn build(&self, base_dir: &Path) -> Result<Background, SceneError> {
+        Ok(match self {
+            BackgroundDesc::Solid(color) => Background::Solid(*color),
+            BackgroundDesc::Gradient { top, bottom } => Background::Gradient {
+                top: *top,
+                bottom: *bottom,
+            },
+            BackgroundDesc::Skybox {
+                right,
+                left,
+                top,
+                bottom,
+                front,
+                back,
+            } => {
+                let mut faces = Vec::with_capacity(6);
+                for path in [right, left, top, bottom, front, back] {
+                    let path = base_dir.join(path);
+                    let face = Texture::load(&path)
+                        .map_err(|error| SceneError::Texture { path, error })?;
+                    faces.push(face);
+                }
+                let faces: [Texture; 6] = faces.try_into().unwrap();
+                Background::Skybox(Rc::new(Cubemap::new(faces)))
+            }
+        })
+    }
+}
+impl Default for BackgroundDesc {
+    fn default() -> Self {
+        BackgroundDesc::Solid([0, 0, 0])
+    }
+}
+
+#[derive(Deserialize)]
+enum LightDesc {
+    Ambient {
+        intensity: f64,
+    },
+    Point {
+        intensity: f64,
+        position: (f64, f64, f64),
+    },
+    Directional {
+        intensity: f64,
+        direction: (f64, f64, f64),
+        #[serde(default)]
+        shadows: bool,
+    },
+    Spot {
+        intensity: f64,
+        position: (f64, f64, f64),
+        direction: (f64, f64, f64),
+        angle: f64,
+        #[serde(default)]
+        shadows: bool,
+    },
+}
+impl LightDesc {
+    fn build(&self) -> Light {
+        match *self {
+            LightDesc::Ambient { intensity } => Light::Ambient { intensity },
+            LightDesc::Point {
+                intensity,
+                position,
+            } => Light::Point {
+                intensity,
+                position: to_vec3(position),
+            },
+            LightDesc::Directional {
+                intensity,
+                direction,
+                shadows,
+            } => Light::Directional {
+                intensity,
+                direction: to_vec3(direction),
+                shadows,
+            },
+            LightDesc::Spot {
+                intensity,
+                position,
+                direction,
+                angle,
+                shadows,
+            } => Light::Spot {
+                intensity,
+                position: to_vec3(position),
+                direction: to_vec3(direction),
+                angle,
+                shadows,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    #[serde(default)]
+    translation: (f64, f64, f64),
+    #[serde(default)]
+    rotation: f64,
+}
+
+/// Linear fog divides by how far it reaches.
+fn check_fog(fog: &Fog) -> Result<(), SceneError> {
+    match fog.falloff {
+        FogFalloff::Linear { start, end } if end > start => Ok(()),
+        FogFalloff::Linear { .. } => Err(SceneError::Invalid(
+            "linear fog has to end further away than it starts".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn to_vec3((x, y, z): (f64, f64, f64)) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+fn one() -> f64 {
+    1.
+}
+fn white() -> [u8; 3] {
+    WHITE
+}
+fn wireframe() -> RenderMode {
+    RenderMode::Wireframe
+}
+fn point_size() -> f64 {
+    DEFAULT_POINT_SIZE
+}
+fn matte() -> f64 {
+    -1.
+}
+
+/// Line number of the first occurrence of `needle`, so errors found after parsing
+/// can still point somewhere useful.
+fn line_of(source: &str, needle: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| line.contains(needle))
+        .map(|i| i + 1)
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, io::Error),
+    Parse(ron::error::SpannedError),
+    Mesh {
+        name: String,
+        path: PathBuf,
+        error: MeshError,
+    },
+    UnknownMesh {
+        name: String,
+        line: Option<usize>,
+    },
+    UnknownNode {
+        name: String,
+        line: Option<usize>,
+    },
+    UnknownJoint {
+        name: String,
+        line: Option<usize>,
+    },
+    Texture {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+    Gltf {
+        path: PathBuf,
+        error: gltf::Error,
+    },
+    Save {
+        path: PathBuf,
+        reason: String,
+    },
+    /// A value that parses but can't be used, like a texture without texels.
+    Invalid(String),
+}
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(path, e) => write!(f, "can't read {}: {}", path.display(), e),
+            SceneError::Parse(e) => write!(
+                f,
+                "line {}, column {}: {}",
+                e.position.line, e.position.col, e.code
+            ),
+            SceneError::Mesh { name, path, error } => {
+                write!(f, "mesh \"{}\" ({}): {}", name, path.display(), error)
+            }
+            SceneError::UnknownMesh {
+                name,
+                line: Some(line),
+            } => write!(f, "line {}: no mesh named \"{}\"", line, name),
+            SceneError::UnknownMesh { name, line: None } => {
+                write!(f, "no mesh named \"{}\"", name)
+            }
+            SceneError::UnknownNode {
+                name,
+                line: Some(line),
+            } => write!(f, "line {}: no instance named \"{}\"", line, name),
+            SceneError::UnknownNode { name, line: None } => {
+                write!(f, "no instance named \"{}\"", name)
+            }
+            SceneError::UnknownJoint {
+                name,
+                line: Some(line),
+            } => write!(f, "line {}: no joint named \"{}\"", line, name),
+            SceneError::UnknownJoint { name, line: None } => {
+                write!(f, "no joint named \"{}\"", name)
+            }
+            SceneError::Texture { path, error } => {
+                write!(f, "texture {}: {}", path.display(), error)
+            }
+            SceneError::Gltf { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneError::Save { path, reason } => {
+                write!(f, "can't save {}: {}", path.display(), reason)
+            }
+            SceneError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+impl std::error::Error for SceneError {}
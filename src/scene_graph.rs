@@ -0,0 +1,109 @@
+use crate::{instance::Instance, instance::Transform};
+
+/// A node places its children, and its instance if it has one, relative to itself.
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: Option<String>,
+    /// Transform relative to the parent, or to the world for roots, as posed this frame.
+    pub local: Transform,
+    /// The authored `local`, what the gizmo edits and the scene file saves. Animations
+    /// start from it every frame and replace the parts they have keys for.
+    pub rest: Transform,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Index into the scene's instances of the mesh drawn at this node.
+    pub instance: Option<usize>,
+}
+
+/// Parent-child hierarchy over the scene's instances. Nodes are only ever appended,
+/// so indices stay valid and parents always come before their children.
+#[derive(Debug, Clone, Default)]
+pub struct SceneGraph {
+    pub nodes: Vec<Node>,
+}
+impl SceneGraph {
+    /// A flat graph with one root per instance, placed where the instance is now.
+    pub fn from_instances(instances: &[Instance]) -> Self {
+        let mut graph = SceneGraph::default();
+        for (i, instance) in instances.iter().enumerate() {
+            graph.add_node(None, instance.trans, Some(i));
+        }
+        graph
+    }
+
+    /// Appends a node and returns its index.
+    pub fn add_node(
+        &mut self,
+        parent: Option<usize>,
+        local: Transform,
+        instance: Option<usize>,
+    ) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            name: None,
+            local,
+            rest: local,
+            parent,
+            children: Vec::new(),
+            instance,
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(index);
+        }
+        index
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&i| self.nodes[i].parent.is_none())
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.name.as_deref() == Some(name))
+    }
+
+    /// The node drawing instance `instance`.
+    pub fn node_of(&self, instance: usize) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.instance == Some(instance))
+    }
+
+    /// Whether `ancestor` is above `node`, directly or not.
+    pub fn is_below(&self, node: usize, ancestor: usize) -> bool {
+        let mut parent = self.nodes[node].parent;
+        while let Some(p) = parent {
+            if p == ancestor {
+                return true;
+            }
+            parent = self.nodes[p].parent;
+        }
+        false
+    }
+
+    /// Composes the local transforms from the roots down.
+    pub fn world_transform(&self, node: usize) -> Transform {
+        let node = &self.nodes[node];
+        match node.parent {
+            Some(parent) => self.world_transform(parent).combine(node.local),
+            None => node.local,
+        }
+    }
+
+    /// Gives every instance its world transform. Call it after changing local
+    /// transforms and before rendering.
+    pub fn update(&self, instances: &mut [Instance]) {
+        let mut world = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let trans = match node.parent {
+                Some(parent) => Transform::combine(world[parent], node.local),
+                None => node.local,
+            };
+            if let Some(instance) = node.instance {
+                instances[instance].trans = trans;
+            }
+            world.push(trans);
+        }
+    }
+}
@@ -0,0 +1,492 @@
+use crate::{
+    consts::*,
+    instance::Transform,
+    scene::{Scene, SceneError},
+};
+use cgmath::*;
+use std::{fs, ops::Range, path::Path};
+
+impl Scene {
+    /// Writes the rest transforms of the scene graph back into the RON file the scene
+    /// was loaded from. Only the `translation`, `rotation` and `scale` of the instances
+    /// change, everything else in the file, comments included, stays as it was.
+    pub fn save_transforms<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        let save_error = |reason: String| SceneError::Save {
+            path: path.into(),
+            reason,
+        };
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if !extension.eq_ignore_ascii_case("ron") {
+            return Err(save_error("only RON scenes can be saved".to_string()));
+        }
+        let source = fs::read_to_string(path).map_err(|e| SceneError::Io(path.into(), e))?;
+        // Nodes get added in the order their instances appear in the file.
+        let transforms: Vec<Transform> = self.graph.nodes.iter().map(|node| node.rest).collect();
+        let patched = patch_transforms(&source, &transforms).map_err(save_error)?;
+        fs::write(path, patched).map_err(|e| save_error(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Open(u8),
+    Close(u8),
+    Colon,
+    Comma,
+    Ident,
+    /// Numbers, strings and anything else that's only ever part of a value.
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+/// Splits RON source into the tokens needed to find struct fields, dropping comments
+/// and whitespace.
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let kind = match bytes[i] {
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                // Block comments nest in RON.
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if bytes[i..].starts_with(b"/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                continue;
+            }
+            b'"' | b'\'' => {
+                let quote = bytes[i];
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+                TokenKind::Other
+            }
+            b'r' if matches!(bytes.get(i + 1), Some(b'"' | b'#')) => {
+                i += 1;
+                let hashes = bytes[i..].iter().take_while(|&&c| c == b'#').count();
+                let mut closing = vec![b'"'];
+                closing.extend(std::iter::repeat_n(b'#', hashes));
+                i += hashes + 1;
+                while i < bytes.len() && !bytes[i..].starts_with(&closing) {
+                    i += 1;
+                }
+                i += closing.len();
+                TokenKind::Other
+            }
+            c @ (b'(' | b'[' | b'{') => {
+                i += 1;
+                TokenKind::Open(c)
+            }
+            c @ (b')' | b']' | b'}') => {
+                i += 1;
+                TokenKind::Close(c)
+            }
+            b':' => {
+                i += 1;
+                TokenKind::Colon
+            }
+            b',' => {
+                i += 1;
+                TokenKind::Comma
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                TokenKind::Ident
+            }
+            _ => {
+                i += 1;
+                while i < bytes.len()
+                    && !bytes[i].is_ascii_whitespace()
+                    && !b"()[]{}:,\"/".contains(&bytes[i])
+                {
+                    i += 1;
+                }
+                TokenKind::Other
+            }
+        };
+        if i > bytes.len() {
+            return Err("unterminated string or comment".to_string());
+        }
+        tokens.push(Token {
+            kind,
+            start,
+            end: i,
+        });
+    }
+    Ok(tokens)
+}
+
+struct Field<'a> {
+    name: &'a str,
+    /// Token indices of the value.
+    value: Range<usize>,
+}
+
+/// What's between the brackets opened at token `open`, split at the top level commas.
+/// Also returns the index of the closing bracket.
+fn items(tokens: &[Token], open: usize) -> Result<(Vec<Range<usize>>, usize), String> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut item_start = open + 1;
+    for (i, token) in tokens.iter().enumerate().skip(open + 1) {
+        match token.kind {
+            TokenKind::Open(_) => depth += 1,
+            TokenKind::Close(_) if depth > 0 => depth -= 1,
+            TokenKind::Close(_) => {
+                if item_start < i {
+                    items.push(item_start..i);
+                }
+                return Ok((items, i));
+            }
+            TokenKind::Comma if depth == 0 => {
+                items.push(item_start..i);
+                item_start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    Err("unbalanced brackets".to_string())
+}
+
+/// The `name: value` fields of the struct opened at token `open`.
+fn fields<'a>(
+    source: &'a str,
+    tokens: &[Token],
+    open: usize,
+) -> Result<(Vec<Field<'a>>, usize), String> {
+    let (items, close) = items(tokens, open)?;
+    let fields = items
+        .into_iter()
+        .map(|item| match &tokens[item.clone()] {
+            [name, colon, _, ..]
+                if name.kind == TokenKind::Ident && colon.kind == TokenKind::Colon =>
+            {
+                Ok(Field {
+                    name: &source[name.start..name.end],
+                    value: item.start + 2..item.end,
+                })
+            }
+            _ => Err(format!(
+                "expected a field at byte {}",
+                tokens[item.start].start
+            )),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((fields, close))
+}
+
+/// Token index of the bracket opening a value, skipping a struct name in front of it.
+fn value_open(tokens: &[Token], value: &Range<usize>, bracket: u8) -> Option<usize> {
+    let mut i = value.start;
+    if tokens.get(i)?.kind == TokenKind::Ident {
+        i += 1;
+    }
+    (i < value.end && tokens[i].kind == TokenKind::Open(bracket)).then_some(i)
+}
+
+/// Index of the first token after the `#![...]` attributes a file may start with.
+fn skip_attributes(source: &str, tokens: &[Token]) -> Result<usize, String> {
+    let mut i = 0;
+    while tokens
+        .get(i)
+        .is_some_and(|token| source[token.start..token.end].starts_with('#'))
+    {
+        if tokens.get(i + 1).map(|token| token.kind) != Some(TokenKind::Open(b'[')) {
+            return Err(format!("expected an attribute at byte {}", tokens[i].start));
+        }
+        let (_, close) = items(tokens, i + 1)?;
+        i = close + 1;
+    }
+    Ok(i)
+}
+
+/// An edit of the source: `text` replaces the bytes in `range`.
+struct Edit {
+    range: Range<usize>,
+    text: String,
+}
+
+/// Replaces the transforms of the instances in a scene file with `transforms`, given
+/// in the order the instances appear. Fields that are missing get added unless the new
+/// value is the default.
+fn patch_transforms(source: &str, transforms: &[Transform]) -> Result<String, String> {
+    let tokens = tokenize(source)?;
+    let start = skip_attributes(source, &tokens)?;
+    let scene = (start..tokens.len())
+        .find(|&i| tokens[i].kind == TokenKind::Open(b'('))
+        .ok_or("there's no scene in the file")?;
+    let (scene_fields, _) = fields(source, &tokens, scene)?;
+
+    let mut edits = Vec::new();
+    let mut patched = 0;
+    if let Some(instances) = scene_fields.iter().find(|field| field.name == "instances") {
+        let open = value_open(&tokens, &instances.value, b'[').ok_or("instances isn't a list")?;
+        let patcher = Patcher {
+            source,
+            tokens: &tokens,
+            transforms,
+        };
+        patcher.patch_list(open, &mut patched, &mut edits)?;
+    }
+    if patched != transforms.len() {
+        return Err("the file changed since the scene was loaded".to_string());
+    }
+
+    let mut result = source.to_string();
+    edits.sort_by_key(|edit| edit.range.start);
+    for edit in edits.into_iter().rev() {
+        result.replace_range(edit.range, &edit.text);
+    }
+    Ok(result)
+}
+
+struct Patcher<'a> {
+    source: &'a str,
+    tokens: &'a [Token],
+    transforms: &'a [Transform],
+}
+impl Patcher<'_> {
+    /// Patches the instances in the list opened at token `open` and their children.
+    /// `patched` counts the instances seen so far, which is the index of the next one's
+    /// transform.
+    fn patch_list(
+        &self,
+        open: usize,
+        patched: &mut usize,
+        edits: &mut Vec<Edit>,
+    ) -> Result<(), String> {
+        let (elements, _) = items(self.tokens, open)?;
+        for element in elements {
+            let open = value_open(self.tokens, &element, b'(').ok_or_else(|| {
+                format!(
+                    "expected an instance at byte {}",
+                    self.tokens[element.start].start
+                )
+            })?;
+            self.patch_instance(open, patched, edits)?;
+        }
+        Ok(())
+    }
+
+    fn patch_instance(
+        &self,
+        open: usize,
+        patched: &mut usize,
+        edits: &mut Vec<Edit>,
+    ) -> Result<(), String> {
+        let transform = self
+            .transforms
+            .get(*patched)
+            .ok_or("the file changed since the scene was loaded")?;
+        *patched += 1;
+        let (fields, close) = fields(self.source, self.tokens, open)?;
+
+        let values = [
+            (
+                "translation",
+                format_vec3(transform.translation),
+                transform.translation == Vec3::zero(),
+            ),
+            (
+                "rotation",
+                format_number(transform.rot),
+                transform.rot == 0.,
+            ),
+            (
+                "scale",
+                format_number(transform.scale),
+                transform.scale == 1.,
+            ),
+        ];
+        let mut missing = Vec::new();
+        for (name, text, is_default) in values {
+            match fields.iter().find(|field| field.name == name) {
+                Some(field) => edits.push(Edit {
+                    range: self.tokens[field.value.start].start
+                        ..self.tokens[field.value.end - 1].end,
+                    text,
+                }),
+                None if !is_default => missing.push(format!("{}: {}", name, text)),
+                None => (),
+            }
+        }
+        if !missing.is_empty() {
+            edits.push(self.insert_fields(open, close, &fields, &missing));
+        }
+
+        if let Some(children) = fields.iter().find(|field| field.name == "children") {
+            let open =
+                value_open(self.tokens, &children.value, b'[').ok_or("children isn't a list")?;
+            self.patch_list(open, patched, edits)?;
+        }
+        Ok(())
+    }
+
+    /// Adds fields after the last one of the struct between tokens `open` and `close`,
+    /// on lines of their own if the struct's fields are.
+    fn insert_fields(&self, open: usize, close: usize, fields: &[Field], new: &[String]) -> Edit {
+        let Some(last) = fields.last() else {
+            let at = self.tokens[open].end;
+            return Edit {
+                range: at..at,
+                text: new.join(", "),
+            };
+        };
+        let first_start = self.tokens[fields[0].value.start - 2].start;
+        let line_start = self.source[..first_start].rfind('\n').map_or(0, |i| i + 1);
+        let before = &self.source[line_start..first_start];
+        let indent = before.chars().all(char::is_whitespace).then_some(before);
+
+        let trailing_comma = last.value.end < close;
+        let mut at = self.tokens[last.value.end - 1].end;
+        let mut text = String::new();
+        if trailing_comma {
+            at = self.tokens[last.value.end].end;
+        } else {
+            text.push(',');
+        }
+        for field in new {
+            match indent {
+                Some(indent) => text += &format!("\n{}{},", indent, field),
+                None => text += &format!(" {},", field),
+            }
+        }
+        if !trailing_comma {
+            text.pop();
+        }
+        Edit {
+            range: at..at,
+            text,
+        }
+    }
+}
+
+fn format_vec3(v: Vec3) -> String {
+    format!(
+        "({}, {}, {})",
+        format_number(v.x),
+        format_number(v.y),
+        format_number(v.z)
+    )
+}
+
+/// Rounds off the error dragging piles up by keeping `SAVED_DIGITS` significant
+/// digits, so small values survive. The shortest form that reads back the same keeps a
+/// decimal point or an exponent, so RON reads it as a float.
+fn format_number(x: f64) -> String {
+    let rounded: f64 = format!("{:.*e}", SAVED_DIGITS - 1, x).parse().unwrap_or(x);
+    format!("{:?}", rounded + 0.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"#![enable(implicit_some)]
+// Comments and layout have to survive saving.
+Scene(
+    meshes: {"cube": Primitive(Cube)},
+    instances: [
+        (
+            name: "parent",
+            mesh: "cube",
+            translation: (1.0, 2.0, 3.0), // Where it starts.
+            children: [
+                (mesh: "cube", scale: 0.5),
+                (
+                    name: "group",
+                    rotation: 30.0,
+                    children: [(mesh: "cube")],
+                ),
+            ],
+        ),
+        (mesh: "cube"),
+    ],
+)
+"#;
+
+    fn load(source: &str) -> Scene {
+        Scene::parse(source, Path::new("")).unwrap()
+    }
+
+    fn rest_transforms(scene: &Scene) -> Vec<Transform> {
+        scene.graph.nodes.iter().map(|node| node.rest).collect()
+    }
+
+    #[test]
+    fn unchanged_transforms_keep_the_file() {
+        let transforms = rest_transforms(&load(SOURCE));
+        assert_eq!(transforms.len(), 5);
+        assert_eq!(patch_transforms(SOURCE, &transforms).unwrap(), SOURCE);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut transforms = rest_transforms(&load(SOURCE));
+        transforms[0].translation = Vec3::new(-1.25, 0., 7.5);
+        transforms[1].rot = 45.;
+        transforms[1].scale = 0.00004;
+        transforms[2].scale = 2.;
+        transforms[3].translation = Vec3::new(0., 1e-7, 0.);
+        transforms[4].rot = -90.;
+        let patched = patch_transforms(SOURCE, &transforms).unwrap();
+
+        assert_eq!(rest_transforms(&load(&patched)), transforms);
+        assert!(patched.starts_with("#![enable(implicit_some)]\n// Comments and layout"));
+        assert!(patched.contains("translation: (-1.25, 0.0, 7.5), // Where it starts."));
+        // Missing fields go on lines of their own when the others are.
+        assert!(patched.contains(
+            "children: [(mesh: \"cube\", translation: (0.0, 1e-7, 0.0))],\n                    scale: 2.0,\n"
+        ));
+        assert!(patched.contains("(mesh: \"cube\", scale: 4e-5, rotation: 45.0)"));
+    }
+
+    #[test]
+    fn rounds_to_significant_digits() {
+        assert_eq!(format_number(1.4999999999), "1.5");
+        assert_eq!(format_number(0.00004000000001), "4e-5");
+        assert_eq!(format_number(-123456.7), "-123457.0");
+        assert_eq!(format_number(-0.), "0.0");
+    }
+
+    #[test]
+    fn rejects_changed_files() {
+        let transforms = rest_transforms(&load(SOURCE));
+        let error = patch_transforms(SOURCE, &transforms[..4]).unwrap_err();
+        assert_eq!(error, "the file changed since the scene was loaded");
+    }
+}
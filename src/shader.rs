@@ -0,0 +1,287 @@
+use crate::{
+    consts::*,
+    instance::{crosses_near_plane, is_back_facing, project_vertex, with_inv_z, Transform},
+    light::Lighting,
+    material::{shade_color, Material},
+    mesh::Mesh,
+    put_pixel, rasterize,
+    skeleton::PosedMesh,
+    stats::RenderStats,
+};
+use cgmath::*;
+use std::{fmt, rc::Rc, time::Instant};
+
+/// Values a vertex shader hands to the fragment shader. The rasterizer blends them
+/// across the triangle, so all it needs is to add and scale them.
+pub trait Varyings: Copy {
+    fn add(self, other: Self) -> Self;
+    fn scale(self, s: f64) -> Self;
+}
+impl Varyings for f64 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+    fn scale(self, s: f64) -> Self {
+        self * s
+    }
+}
+impl<const N: usize> Varyings for [f64; N] {
+    fn add(mut self, other: Self) -> Self {
+        for (a, b) in self.iter_mut().zip(other) {
+            *a += b;
+        }
+        self
+    }
+    fn scale(self, s: f64) -> Self {
+        self.map(|a| a * s)
+    }
+}
+impl Varyings for Vec2 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+    fn scale(self, s: f64) -> Self {
+        self * s
+    }
+}
+impl Varyings for Vec3 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+    fn scale(self, s: f64) -> Self {
+        self * s
+    }
+}
+impl Varyings for Vec4 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+    fn scale(self, s: f64) -> Self {
+        self * s
+    }
+}
+impl<A: Varyings, B: Varyings> Varyings for (A, B) {
+    fn add(self, other: Self) -> Self {
+        (self.0.add(other.0), self.1.add(other.1))
+    }
+    fn scale(self, s: f64) -> Self {
+        (self.0.scale(s), self.1.scale(s))
+    }
+}
+
+/// Everything that stays the same for all vertices and pixels of a draw.
+pub struct Uniforms<'a> {
+    pub model: Transform,
+    pub cam_trans: Transform,
+    pub material: &'a Material,
+    pub lighting: &'a Lighting,
+    /// The skinned vertices and normals, handed to the vertex shader instead of the
+    /// mesh's own.
+    pub posed: Option<&'a PosedMesh>,
+}
+
+/// A mesh vertex in object space. `uv` is zero for meshes without uvs.
+pub struct VertexInput {
+    pub index: usize,
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+}
+
+pub trait VertexShader {
+    type Varyings: Varyings;
+    /// Returns the camera space position of the vertex, which the pipeline projects
+    /// onto the canvas, and the varyings to interpolate for the fragment shader.
+    fn shade_vertex(&self, vertex: &VertexInput, uniforms: &Uniforms) -> (Vec3, Self::Varyings);
+}
+
+pub trait FragmentShader<V: Varyings> {
+    /// Color of a pixel, or `None` to discard it without touching the depth buffer.
+    fn shade_fragment(&self, varyings: V, uniforms: &Uniforms) -> Option<[u8; 3]>;
+}
+
+/// A vertex and fragment shader pair with the types erased, so materials can hold one.
+pub trait Shader {
+    fn render(
+        &self,
+        mesh: &Mesh,
+        tris: &[Indices],
+        uniforms: &Uniforms,
+        frame: &mut [u8],
+        depth_buffer: &mut [f64],
+        stats: &mut RenderStats,
+    );
+}
+
+pub struct ShaderProgram<VS, FS> {
+    pub vertex: VS,
+    pub fragment: FS,
+    pub cull_back_faces: bool,
+}
+impl<VS, FS> ShaderProgram<VS, FS> {
+    pub fn new(vertex: VS, fragment: FS) -> Self {
+        Self {
+            vertex,
+            fragment,
+            cull_back_faces: true,
+        }
+    }
+}
+impl<VS, FS> Shader for ShaderProgram<VS, FS>
+where
+    VS: VertexShader,
+    FS: FragmentShader<VS::Varyings>,
+{
+    fn render(
+        &self,
+        mesh: &Mesh,
+        tris: &[Indices],
+        uniforms: &Uniforms,
+        frame: &mut [u8],
+        depth_buffer: &mut [f64],
+        stats: &mut RenderStats,
+    ) {
+        let transform_start = Instant::now();
+        let mut positions = Vec::with_capacity(mesh.verts.len());
+        let mut varyings = Vec::with_capacity(mesh.verts.len());
+        for index in 0..mesh.verts.len() {
+            let (position, normal) = match uniforms.posed {
+                Some(posed) => (posed.verts[index], posed.normals[index]),
+                None => (mesh.verts[index], mesh.normals[index]),
+            };
+            let vertex = VertexInput {
+                index,
+                position,
+                normal,
+                uv: mesh.uvs.as_ref().map_or(Vec2::zero(), |uvs| uvs[index]),
+            };
+            let (position, vert_varyings) = self.vertex.shade_vertex(&vertex, uniforms);
+            positions.push(position);
+            varyings.push(vert_varyings);
+        }
+        stats.transform_time += transform_start.elapsed();
+
+        let raster_start = Instant::now();
+        for tri in tris {
+            let corners = [tri.0, tri.1, tri.2];
+            if crosses_near_plane(corners.map(|v| positions[v])) {
+                stats.triangles_clipped += 1;
+                continue;
+            }
+            if self.cull_back_faces && is_back_facing(corners.map(|v| positions[v])) {
+                stats.triangles_culled += 1;
+                continue;
+            }
+            stats.triangles_rasterized += 1;
+            let points = corners.map(|v| with_inv_z(project_vertex(positions[v]), positions[v]));
+            rasterize(
+                points,
+                corners.map(|v| varyings[v]),
+                CANVAS_SIZE,
+                |i, z, pixel_varyings| {
+                    if z <= depth_buffer[i] {
+                        return;
+                    }
+                    if let Some(color) = self.fragment.shade_fragment(pixel_varyings, uniforms) {
+                        depth_buffer[i] = z;
+                        put_pixel(frame, i, color);
+                        stats.pixels_written += 1;
+                    }
+                },
+            );
+        }
+        stats.raster_time += raster_start.elapsed();
+    }
+}
+
+/// Lets `Material` hold a shader and still derive `Debug` and `Clone`.
+#[derive(Clone)]
+pub struct CustomShader(pub Rc<dyn Shader>);
+impl fmt::Debug for CustomShader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CustomShader")
+    }
+}
+
+/// The built-in vertex stage: moves the vertex into camera space and passes on the
+/// camera space (normal, position) for lighting.
+pub struct StandardVertex;
+impl VertexShader for StandardVertex {
+    type Varyings = (Vec3, Vec3);
+    fn shade_vertex(&self, vertex: &VertexInput, uniforms: &Uniforms) -> (Vec3, (Vec3, Vec3)) {
+        let mut position = vertex.position;
+        uniforms
+            .model
+            .apply_transform(&mut position, uniforms.cam_trans);
+        let normal = uniforms
+            .model
+            .transform_normal(vertex.normal, uniforms.cam_trans);
+        (position, (normal, position))
+    }
+}
+
+/// Cel shading: lights per pixel, then snaps the intensity to a few flat bands.
+pub struct Toon {
+    pub bands: usize,
+}
+impl FragmentShader<(Vec3, Vec3)> for Toon {
+    fn shade_fragment(
+        &self,
+        (normal, point): (Vec3, Vec3),
+        uniforms: &Uniforms,
+    ) -> Option<[u8; 3]> {
+        let intensity =
+            uniforms
+                .lighting
+                .compute_lighting(point, normal, uniforms.material.specular);
+        let bands = self.bands.max(1) as f64;
+        let banded = ((intensity * bands).floor() + 0.5) / bands;
+        Some(shade_color(uniforms.material.base_color, banded.min(1.)))
+    }
+}
+
+pub fn toon_shader(bands: usize) -> CustomShader {
+    CustomShader(Rc::new(ShaderProgram::new(StandardVertex, Toon { bands })))
+}
+
+/// Passes one scalar per vertex, e.g. simulation results, on to `HeatMap`.
+pub struct ScalarField {
+    pub values: Vec<f64>,
+}
+impl VertexShader for ScalarField {
+    type Varyings = f64;
+    fn shade_vertex(&self, vertex: &VertexInput, uniforms: &Uniforms) -> (Vec3, f64) {
+        let mut position = vertex.position;
+        uniforms
+            .model
+            .apply_transform(&mut position, uniforms.cam_trans);
+        (
+            position,
+            self.values.get(vertex.index).copied().unwrap_or(0.),
+        )
+    }
+}
+
+/// Maps a scalar from [min, max] onto a blue - green - red ramp.
+pub struct HeatMap {
+    pub min: f64,
+    pub max: f64,
+}
+impl FragmentShader<f64> for HeatMap {
+    fn shade_fragment(&self, value: f64, _: &Uniforms) -> Option<[u8; 3]> {
+        let t = ((value - self.min) / (self.max - self.min)).clamp(0., 1.);
+        let (r, g, b) = if t < 0.5 {
+            (0., t * 2., 1. - t * 2.)
+        } else {
+            (t * 2. - 1., 2. - t * 2., 0.)
+        };
+        Some([(r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8])
+    }
+}
+
+pub fn heat_map_shader(values: Vec<f64>, min: f64, max: f64) -> CustomShader {
+    let mut program = ShaderProgram::new(ScalarField { values }, HeatMap { min, max });
+    // Scalar data is usually looked at from all sides, inside included.
+    program.cull_back_faces = false;
+    CustomShader(Rc::new(program))
+}
@@ -0,0 +1,181 @@
+use crate::{
+    consts::*,
+    instance::{Instance, Transform},
+    light::Light,
+    rasterize,
+};
+use cgmath::*;
+
+/// Depth of the scene as seen from a light, rendered with the same rasterizer as the
+/// screen. Everything is in camera space, so the map follows the camera around.
+pub struct ShadowMap {
+    /// Rotates camera space into light space, where the light looks down +z.
+    basis: Matrix3<f64>,
+    origin: Vec3,
+    projection: Projection,
+    depth: Vec<f64>,
+}
+
+enum Projection {
+    /// Spot lights. Stores 1/z like the screen depth buffer.
+    Perspective { scale: f64 },
+    /// Directional lights. Stores `far - z`, which stays linear in screen space.
+    Orthographic { scale: f64, center: Vec2, far: f64 },
+}
+
+impl ShadowMap {
+    /// Renders the shadow map for `light`, which should already be in camera space.
+    /// Returns `None` for lights that don't cast shadows.
+    pub fn render(light: &Light, instances: &[Instance], cam_trans: Transform) -> Option<Self> {
+        let casts_shadows = matches!(
+            light,
+            Light::Directional { shadows: true, .. } | Light::Spot { shadows: true, .. }
+        );
+        if !casts_shadows {
+            return None;
+        }
+        let meshes: Vec<(Vec<Vec3>, &Instance)> = instances
+            .iter()
+            .map(|inst| (inst.camera_space_verts(cam_trans), inst))
+            .collect();
+
+        let mut shadow_map = match *light {
+            Light::Directional {
+                direction,
+                shadows: true,
+                ..
+            } => {
+                let basis = light_basis(direction);
+                // Fit the map around everything that can cast a shadow.
+                let (mut min, mut max) = (Vec3::from_value(f64::MAX), Vec3::from_value(f64::MIN));
+                for (verts, _) in &meshes {
+                    for v in verts {
+                        let l = basis * v;
+                        min = Vec3::new(min.x.min(l.x), min.y.min(l.y), min.z.min(l.z));
+                        max = Vec3::new(max.x.max(l.x), max.y.max(l.y), max.z.max(l.z));
+                    }
+                }
+                if min.x > max.x {
+                    return None;
+                }
+                let half_extent = ((max.x - min.x).max(max.y - min.y) / 2.).max(f64::EPSILON);
+                ShadowMap {
+                    basis,
+                    origin: Vec3::zero(),
+                    projection: Projection::Orthographic {
+                        scale: SHADOW_MAP_SIZE as f64 / 2. / half_extent,
+                        center: Vec2::new(min.x + max.x, min.y + max.y) / 2.,
+                        far: max.z + 1.,
+                    },
+                    depth: vec![0.; (SHADOW_MAP_SIZE * SHADOW_MAP_SIZE) as usize],
+                }
+            }
+            Light::Spot {
+                position,
+                direction,
+                angle,
+                shadows: true,
+                ..
+            } => ShadowMap {
+                basis: light_basis(direction),
+                origin: position,
+                projection: Projection::Perspective {
+                    scale: SHADOW_MAP_SIZE as f64 / 2. / Deg(angle.min(89.)).tan(),
+                },
+                depth: vec![0.; (SHADOW_MAP_SIZE * SHADOW_MAP_SIZE) as usize],
+            },
+            _ => return None,
+        };
+
+        for (verts, inst) in &meshes {
+            let projected: Vec<Option<Vec3>> =
+                verts.iter().map(|v| shadow_map.project(*v)).collect();
+            for tri in &inst.mesh.triangles {
+                // Triangles reaching behind a spot light are skipped rather than clipped.
+                if let (Some(p0), Some(p1), Some(p2)) =
+                    (projected[tri.0], projected[tri.1], projected[tri.2])
+                {
+                    let depth = &mut shadow_map.depth;
+                    rasterize([p0, p1, p2], [[]; 3], SHADOW_MAP_SIZE, |i, z, _| {
+                        if z > depth[i] {
+                            depth[i] = z;
+                        }
+                    });
+                }
+            }
+        }
+        Some(shadow_map)
+    }
+
+    /// Position on the map in pixels from its center, with the stored depth value as z.
+    fn project(&self, point: Vec3) -> Option<Vec3> {
+        let l = self.basis * (point - self.origin);
+        match self.projection {
+            Projection::Perspective { scale } => {
+                if l.z < SHADOW_NEAR {
+                    return None;
+                }
+                Some(Vec3::new(l.x / l.z * scale, l.y / l.z * scale, 1. / l.z))
+            }
+            Projection::Orthographic { scale, center, far } => Some(Vec3::new(
+                (l.x - center.x) * scale,
+                (l.y - center.y) * scale,
+                far - l.z,
+            )),
+        }
+    }
+
+    /// Light space depth belonging to a value stored in the map.
+    fn stored_to_z(&self, stored: f64) -> f64 {
+        match self.projection {
+            Projection::Perspective { .. } => 1. / stored,
+            Projection::Orthographic { far, .. } => far - stored,
+        }
+    }
+
+    /// Fraction of the light reaching `point`, averaged over the surrounding texels
+    /// (percentage closer filtering) to soften the jagged shadow edges.
+    pub fn visibility(&self, point: Vec3) -> f64 {
+        let projected = match self.project(point) {
+            Some(projected) => projected,
+            None => return 1.,
+        };
+        let z = self.stored_to_z(projected.z);
+        let half_size = SHADOW_MAP_SIZE as i32 / 2;
+        let (x, y) = (
+            projected.x as i32 + half_size,
+            -(projected.y as i32) + half_size,
+        );
+
+        let (mut lit, mut samples) = (0, 0);
+        for dy in -SHADOW_PCF_RADIUS..=SHADOW_PCF_RADIUS {
+            for dx in -SHADOW_PCF_RADIUS..=SHADOW_PCF_RADIUS {
+                let (sx, sy) = (x + dx, y + dy);
+                samples += 1;
+                if sx < 0 || sy < 0 || sx >= SHADOW_MAP_SIZE as i32 || sy >= SHADOW_MAP_SIZE as i32
+                {
+                    lit += 1;
+                    continue;
+                }
+                let stored = self.depth[sy as usize * SHADOW_MAP_SIZE as usize + sx as usize];
+                if stored == 0. || z <= self.stored_to_z(stored) + SHADOW_BIAS {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f64 / samples as f64
+    }
+}
+
+/// Rotation whose rows are the light's right, up and forward axes.
+fn light_basis(direction: Vec3) -> Matrix3<f64> {
+    let forward = direction.normalize();
+    let helper = if forward.y.abs() < 0.99 {
+        Vec3::unit_y()
+    } else {
+        Vec3::unit_x()
+    };
+    let right = helper.cross(forward).normalize();
+    let up = forward.cross(right);
+    Matrix3::from_cols(right, up, forward).transpose()
+}
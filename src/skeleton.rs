@@ -0,0 +1,274 @@
+use crate::{
+    animation::Animation,
+    consts::*,
+    instance::Transform,
+    mesh::{normalize_or_zero, Mesh},
+};
+use cgmath::*;
+use std::rc::Rc;
+
+/// The joints moving a vertex, with weights summing to 1. Unused slots have weight 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JointWeights {
+    pub joints: [usize; 4],
+    pub weights: [f64; 4],
+}
+
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    /// Rest pose relative to the parent joint, or to the mesh for roots.
+    pub rest: Transform,
+    /// Moves a mesh vertex into the joint's space in the rest pose.
+    pub inverse_bind: Transform,
+}
+
+/// Joint hierarchy of a skinned mesh. Like `SceneGraph`, parents always come before
+/// their children.
+#[derive(Debug, Clone, Default)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+}
+impl Skeleton {
+    /// Appends a joint posed at `rest` and returns its index. The mesh is taken to be
+    /// bound in the rest pose.
+    pub fn add_joint(&mut self, name: &str, parent: Option<usize>, rest: Transform) -> usize {
+        let index = self.joints.len();
+        assert!(parent.is_none_or(|p| p < index));
+        let world = match parent {
+            Some(parent) => self.rest_world_transform(parent).combine(rest),
+            None => rest,
+        };
+        self.joints.push(Joint {
+            name: name.to_string(),
+            parent,
+            rest,
+            inverse_bind: world.inverse(),
+        });
+        index
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    fn rest_world_transform(&self, joint: usize) -> Transform {
+        let joint = &self.joints[joint];
+        match joint.parent {
+            Some(parent) => self.rest_world_transform(parent).combine(joint.rest),
+            None => joint.rest,
+        }
+    }
+
+    /// Transforms taking bound vertices to where the joints posed at `locals` put them.
+    pub fn skinning_transforms(&self, locals: &[Transform]) -> Vec<Transform> {
+        let mut world: Vec<Transform> = Vec::with_capacity(self.joints.len());
+        for (joint, &local) in self.joints.iter().zip(locals) {
+            world.push(match joint.parent {
+                Some(parent) => world[parent].combine(local),
+                None => local,
+            });
+        }
+        world
+            .iter()
+            .zip(&self.joints)
+            .map(|(world, joint)| world.combine(joint.inverse_bind))
+            .collect()
+    }
+
+    /// Weights every vertex by its distance to the two nearest bones, a bone being
+    /// the line from a joint to one of its children. Good enough for tubes and limbs
+    /// that come without weights of their own.
+    pub fn weights_by_distance(&self, verts: &Vertices) -> Vec<JointWeights> {
+        let positions: Vec<Vec3> = (0..self.joints.len())
+            .map(|j| self.rest_world_transform(j).translation)
+            .collect();
+        let mut bones: Vec<Vec<(Vec3, Vec3)>> = vec![Vec::new(); self.joints.len()];
+        for (j, joint) in self.joints.iter().enumerate() {
+            if let Some(parent) = joint.parent {
+                bones[parent].push((positions[parent], positions[j]));
+            }
+        }
+        // Joints without children still pull on the vertices around them.
+        for (j, bones) in bones.iter_mut().enumerate() {
+            if bones.is_empty() {
+                bones.push((positions[j], positions[j]));
+            }
+        }
+
+        verts
+            .iter()
+            .map(|&v| {
+                let mut nearest: Vec<(usize, f64)> = bones
+                    .iter()
+                    .enumerate()
+                    .map(|(j, bones)| {
+                        let dist = bones
+                            .iter()
+                            .map(|&(a, b)| dist_to_segment(v, a, b))
+                            .fold(f64::MAX, f64::min);
+                        (j, dist)
+                    })
+                    .collect();
+                nearest.sort_by(|a, b| a.1.total_cmp(&b.1));
+                nearest.truncate(2);
+
+                let mut weights = JointWeights::default();
+                let inv_dists = nearest.iter().map(|&(_, d)| 1. / (d * d + 1e-6));
+                let total: f64 = inv_dists.clone().sum();
+                for (slot, ((j, _), inv_dist)) in nearest.iter().zip(inv_dists).enumerate() {
+                    weights.joints[slot] = *j;
+                    weights.weights[slot] = inv_dist / total;
+                }
+                weights
+            })
+            .collect()
+    }
+}
+
+/// Joint animations for a skeleton. The channels' `node` is the joint they move.
+#[derive(Debug, Clone, Default)]
+pub struct Clip {
+    pub name: String,
+    pub channels: Vec<Animation>,
+}
+impl Clip {
+    pub fn duration(&self) -> f64 {
+        crate::animation::duration(&self.channels)
+    }
+}
+
+/// Object space vertices and normals of a skinned mesh, posed once a frame so
+/// everything drawing or testing the mesh can share them.
+#[derive(Debug, Clone)]
+pub struct PosedMesh {
+    pub verts: Vertices,
+    pub normals: Vec<Vec3>,
+}
+
+/// Poses a mesh with linear blend skinning: every vertex is moved by each of its
+/// joints and the results are averaged by the weights.
+#[derive(Debug, Clone)]
+pub struct Skin {
+    pub skeleton: Rc<Skeleton>,
+    pub clips: Vec<Clip>,
+    /// Index into `clips` of the clip that's playing, the rest pose if `None`.
+    pub clip: Option<usize>,
+    joint_transforms: Vec<Transform>,
+    /// Weights for a mesh that came without its own, see `bind`.
+    weights: Option<Vec<JointWeights>>,
+}
+impl Skin {
+    /// Starts out playing the first clip, if any.
+    pub fn new(skeleton: Rc<Skeleton>, clips: Vec<Clip>) -> Self {
+        let mut skin = Self {
+            skeleton,
+            clip: if clips.is_empty() { None } else { Some(0) },
+            clips,
+            joint_transforms: Vec::new(),
+            weights: None,
+        };
+        skin.pose(0.);
+        skin
+    }
+
+    /// Prepares the skin for deforming `mesh`. A mesh without joint weights gets
+    /// weighted by `Skeleton::weights_by_distance`, kept here so the mesh itself can
+    /// stay shared.
+    pub fn bind(&mut self, mesh: &Mesh) {
+        self.weights = match mesh.joint_weights {
+            Some(_) => None,
+            None => Some(self.skeleton.weights_by_distance(&mesh.verts)),
+        };
+    }
+
+    /// Poses the skeleton `time` seconds into the current clip.
+    pub fn pose(&mut self, time: f64) {
+        let mut locals: Vec<Transform> = self.skeleton.joints.iter().map(|j| j.rest).collect();
+        if let Some(clip) = self.clip.and_then(|i| self.clips.get(i)) {
+            for channel in &clip.channels {
+                channel.apply(time, &mut locals[channel.node]);
+            }
+        }
+        self.joint_transforms = self.skeleton.skinning_transforms(&locals);
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.clip
+            .and_then(|i| self.clips.get(i))
+            .map_or(0., Clip::duration)
+    }
+
+    /// The vertices and normals of `mesh` in the current pose.
+    pub fn pose_mesh(&self, mesh: &Mesh) -> PosedMesh {
+        let count = mesh.verts.len();
+        PosedMesh {
+            verts: (0..count).map(|i| self.point(mesh, i)).collect(),
+            normals: (0..count)
+                .map(|i| self.direction(mesh, i, mesh.normals[i]))
+                .collect(),
+        }
+    }
+
+    /// The mesh's own joint weights, or the ones `bind` made for it.
+    fn weights<'a>(&'a self, mesh: &'a Mesh) -> Option<&'a Vec<JointWeights>> {
+        mesh.joint_weights.as_ref().or(self.weights.as_ref())
+    }
+
+    /// Position of vertex `i` of `mesh` in the current pose. Vertices without joint
+    /// weights stay put.
+    pub fn point(&self, mesh: &Mesh, i: usize) -> Vec3 {
+        let vert = mesh.verts[i];
+        self.weights(mesh)
+            .and_then(|weights| self.blend(&weights[i], |trans| trans.to_parent_space(vert)))
+            .unwrap_or(vert)
+    }
+
+    /// Turns a direction at vertex `i`, like its normal, along with the vertex.
+    pub fn direction(&self, mesh: &Mesh, i: usize, dir: Vec3) -> Vec3 {
+        self.weights(mesh)
+            .and_then(|weights| self.blend(&weights[i], |trans| trans.rotate(dir)))
+            .map_or(dir, normalize_or_zero)
+    }
+
+    /// `None` when the weights add up to nothing, which would pull the vertex to the
+    /// origin.
+    fn blend(&self, weights: &JointWeights, transform: impl Fn(Transform) -> Vec3) -> Option<Vec3> {
+        let mut blended = Vec3::zero();
+        let mut total = 0.;
+        for (&joint, &weight) in weights.joints.iter().zip(&weights.weights) {
+            if weight > 0. {
+                blended += transform(self.joint_transforms[joint]) * weight;
+                total += weight;
+            }
+        }
+        (total > 0.).then_some(blended)
+    }
+}
+
+fn dist_to_segment(p: Vec3, a: Vec3, b: Vec3) -> f64 {
+    let ab = b - a;
+    let len2 = ab.magnitude2();
+    if len2 == 0. {
+        return p.distance(a);
+    }
+    let t = ((p - a).dot(ab) / len2).clamp(0., 1.);
+    p.distance(a + ab * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    #[test]
+    fn vertices_without_weights_stay_put() {
+        let cube = primitives::cube();
+        let mut skin = Skin::new(Rc::new(Skeleton::default()), Vec::new());
+        skin.bind(&cube);
+        let posed = skin.pose_mesh(&cube);
+        assert_eq!(posed.verts, cube.verts);
+        assert_eq!(posed.normals, cube.normals);
+    }
+}
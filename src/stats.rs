@@ -0,0 +1,98 @@
+use std::{
+    fs::File,
+    io::{self, LineWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+/// Counters and stage timings of a single frame.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    pub instances_submitted: usize,
+    /// Instances `clip_scene` dropped for reaching outside the view.
+    pub instances_culled: usize,
+    pub triangles_submitted: usize,
+    /// Triangles of the instances `clip_scene` dropped, plus the filled ones dropped for
+    /// reaching past the near plane.
+    pub triangles_clipped: usize,
+    /// Back faces skipped before rasterization.
+    pub triangles_culled: usize,
+    pub triangles_rasterized: usize,
+    /// Pixels that passed the depth test. A pixel drawn over counts every time.
+    pub pixels_written: usize,
+    /// Pixels showing geometry at the end of the frame.
+    pub pixels_covered: usize,
+    /// Moving vertices, normals and tangents into camera space, custom vertex shaders
+    /// included.
+    pub transform_time: Duration,
+    pub clipping_time: Duration,
+    /// Lights moved into camera space and their shadow maps rendered.
+    pub lighting_time: Duration,
+    pub raster_time: Duration,
+    /// Handing the finished frame to the window.
+    pub present_time: Duration,
+}
+impl RenderStats {
+    /// How often every visible pixel got written on average, 1 means no overdraw.
+    pub fn overdraw(&self) -> f64 {
+        if self.pixels_covered == 0 {
+            0.
+        } else {
+            self.pixels_written as f64 / self.pixels_covered as f64
+        }
+    }
+
+    /// Sum of the stage timings.
+    pub fn total_time(&self) -> Duration {
+        self.transform_time
+            + self.clipping_time
+            + self.lighting_time
+            + self.raster_time
+            + self.present_time
+    }
+}
+
+/// Appends one line of `RenderStats` per frame to a CSV file, times in milliseconds.
+pub struct StatsCsv {
+    // Flushes every line, the event loop exits the process without running destructors.
+    writer: LineWriter<File>,
+    frame: u64,
+}
+impl StatsCsv {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut writer = LineWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "frame,instances_submitted,instances_culled,triangles_submitted,triangles_clipped,\
+             triangles_culled,triangles_rasterized,pixels_written,pixels_covered,overdraw,\
+             transform_ms,clipping_ms,lighting_ms,raster_ms,present_ms,total_ms"
+        )?;
+        Ok(Self { writer, frame: 0 })
+    }
+
+    pub fn write(&mut self, stats: &RenderStats) -> io::Result<()> {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.;
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3}",
+            self.frame,
+            stats.instances_submitted,
+            stats.instances_culled,
+            stats.triangles_submitted,
+            stats.triangles_clipped,
+            stats.triangles_culled,
+            stats.triangles_rasterized,
+            stats.pixels_written,
+            stats.pixels_covered,
+            stats.overdraw(),
+            ms(stats.transform_time),
+            ms(stats.clipping_time),
+            ms(stats.lighting_time),
+            ms(stats.raster_time),
+            ms(stats.present_time),
+            ms(stats.total_time()),
+        )?;
+        self.frame += 1;
+        Ok(())
+    }
+}
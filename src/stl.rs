@@ -0,0 +1,190 @@
+use crate::{
+    consts::*,
+    mesh::{normalize_or_zero, pos_key, Mesh, MeshError},
+};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+impl Mesh {
+    /// Loads an ASCII or binary STL file. Triangles sharing a corner position get
+    /// joined, and the normals are generated since STL only has one per face.
+    pub fn load_stl<P: AsRef<Path>>(path: P) -> Result<Mesh, MeshError> {
+        let bytes = fs::read(path)?;
+        Mesh::parse_stl(&bytes)
+    }
+
+    pub fn parse_stl(bytes: &[u8]) -> Result<Mesh, MeshError> {
+        // Binary files may start with "solid" too, their size gives them away.
+        let corners = if is_binary_stl(bytes) {
+            parse_binary_stl(bytes)
+        } else if bytes.len() >= HEADER_SIZE + 4 && !bytes.trim_ascii_start().starts_with(b"solid")
+        {
+            // ASCII files always open with "solid", so this is a binary one cut short.
+            return Err(MeshError::Invalid("the file ends early".to_string()));
+        } else {
+            let source = std::str::from_utf8(bytes)
+                .map_err(|_| MeshError::Invalid("not an STL file".to_string()))?;
+            parse_ascii_stl(source)?
+        };
+        if corners.is_empty() {
+            return Err(MeshError::Invalid("the file has no triangles".to_string()));
+        }
+
+        let mut verts = Vec::new();
+        let mut vert_at: HashMap<[u64; 3], usize> = HashMap::new();
+        let indices: Vec<usize> = corners
+            .iter()
+            .map(|&corner| {
+                *vert_at.entry(pos_key(corner)).or_insert_with(|| {
+                    verts.push(corner);
+                    verts.len() - 1
+                })
+            })
+            .collect();
+        let triangles = indices
+            .chunks_exact(3)
+            .map(|t| (t[0], t[1], t[2]))
+            .collect();
+        Ok(Mesh::new(verts, triangles, None))
+    }
+
+    pub fn save_stl<P: AsRef<Path>>(&self, path: P, binary: bool) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_stl(&mut writer, binary)?;
+        writer.flush()
+    }
+
+    /// Writes the triangles with their face normals. Everything else about the mesh
+    /// is lost, STL has no room for it.
+    pub fn write_stl<W: Write>(&self, writer: &mut W, binary: bool) -> io::Result<()> {
+        let faces = self.triangles.iter().map(|tri| {
+            let corners = [tri.0, tri.1, tri.2].map(|v| self.verts[v]);
+            let normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+            (normalize_or_zero(normal), corners)
+        });
+
+        if binary {
+            let mut header = [0u8; HEADER_SIZE];
+            let title = b"binary STL";
+            header[..title.len()].copy_from_slice(title);
+            writer.write_all(&header)?;
+            writer.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+            for (normal, corners) in faces {
+                for v in [normal, corners[0], corners[1], corners[2]] {
+                    for c in [v.x, v.y, v.z] {
+                        writer.write_all(&(c as f32).to_le_bytes())?;
+                    }
+                }
+                // Attribute byte count, unused.
+                writer.write_all(&[0, 0])?;
+            }
+        } else {
+            writeln!(writer, "solid mesh")?;
+            for (normal, corners) in faces {
+                writeln!(
+                    writer,
+                    "  facet normal {} {} {}",
+                    normal.x, normal.y, normal.z
+                )?;
+                writeln!(writer, "    outer loop")?;
+                for v in corners {
+                    writeln!(writer, "      vertex {} {} {}", v.x, v.y, v.z)?;
+                }
+                writeln!(writer, "    endloop")?;
+                writeln!(writer, "  endfacet")?;
+            }
+            writeln!(writer, "endsolid mesh")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_binary_stl(bytes: &[u8]) -> bool {
+    if bytes.len() < HEADER_SIZE + 4 {
+        return false;
+    }
+    let count = u32::from_le_bytes(bytes[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap());
+    bytes.len() == HEADER_SIZE + 4 + count as usize * TRIANGLE_SIZE
+}
+
+/// Corner positions, three per triangle.
+fn parse_binary_stl(bytes: &[u8]) -> Vertices {
+    let read_vec3 = |at: &[u8]| {
+        let c = |i: usize| f32::from_le_bytes(at[i * 4..i * 4 + 4].try_into().unwrap()) as f64;
+        Vec3::new(c(0), c(1), c(2))
+    };
+    bytes[HEADER_SIZE + 4..]
+        .chunks_exact(TRIANGLE_SIZE)
+        // Skip the face normal, it gets recomputed.
+        .flat_map(|tri| [12, 24, 36].map(|at| read_vec3(&tri[at..])))
+        .collect()
+}
+
+fn parse_ascii_stl(source: &str) -> Result<Vertices, MeshError> {
+    let mut corners = Vec::new();
+    for (line_index, line) in source.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        if words.next() == Some("vertex") {
+            let mut coord = || {
+                let word = words.next().unwrap_or("");
+                word.parse().map_err(|_| MeshError::Parse {
+                    line: line_index + 1,
+                    msg: format!("'{}' is not a number", word),
+                })
+            };
+            corners.push(Vec3::new(coord()?, coord()?, coord()?));
+        }
+    }
+    if corners.len() % 3 != 0 {
+        return Err(MeshError::Invalid(
+            "the vertex count isn't a multiple of 3".to_string(),
+        ));
+    }
+    Ok(corners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn corners(mesh: &Mesh) -> Vertices {
+        let tris = mesh.triangles.iter();
+        tris.flat_map(|tri| [tri.0, tri.1, tri.2].map(|v| mesh.verts[v]))
+            .collect()
+    }
+
+    fn round_trip(binary: bool) {
+        let mesh = primitives::cube();
+        let mut bytes = Vec::new();
+        mesh.write_stl(&mut bytes, binary).unwrap();
+        let loaded = Mesh::parse_stl(&bytes).unwrap();
+        assert_eq!(corners(&loaded), corners(&mesh));
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        round_trip(false);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        round_trip(true);
+    }
+
+    #[test]
+    fn truncated_binary_body() {
+        let mut bytes = Vec::new();
+        primitives::cube().write_stl(&mut bytes, true).unwrap();
+        bytes.truncate(bytes.len() - TRIANGLE_SIZE / 2);
+        let error = Mesh::parse_stl(&bytes).unwrap_err();
+        assert_eq!(error.to_string(), "the file ends early");
+    }
+}